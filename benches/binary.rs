@@ -0,0 +1,36 @@
+#![feature(test)]
+extern crate test;
+extern crate rsvm;
+
+use rsvm::{DataVec,SvmProblem};
+use test::Bencher;
+
+// 10k samples with 50 of 100 features each, as SVMLight text and in the binary format
+fn dataset() -> (Vec<u8>, Vec<u8>) {
+    let prob = SvmProblem::from_pairs((0..10_000).map(|i| {
+        let x: Vec<f64> = (0..100).map(|j| if (i + j) % 2 == 0 { (i * j) as f64 / 7.0 } else { 0.0 }).collect();
+        (if i % 2 == 0 { 1.0 } else { -1.0 }, DataVec::from_dense(x))
+    }));
+
+    let mut text = Vec::new();
+    for (label, v) in &prob {
+        text.extend(format!("{} {}\n", label, v).into_bytes());
+    }
+
+    let mut binary = Vec::new();
+    prob.write_binary(&mut binary).unwrap();
+
+    (text, binary)
+}
+
+#[bench]
+fn read_svmlight_10k(b: &mut Bencher) {
+    let (text, _) = dataset();
+    b.iter(|| SvmProblem::from_svmlight_reader(&text[..]).unwrap());
+}
+
+#[bench]
+fn read_binary_10k(b: &mut Bencher) {
+    let (_, binary) = dataset();
+    b.iter(|| SvmProblem::read_binary(&binary[..]).unwrap());
+}
@@ -0,0 +1,38 @@
+#![feature(test)]
+extern crate test;
+extern crate rsvm;
+
+use rsvm::{DataVec,SvmNode};
+use test::Bencher;
+
+const DIM: usize = 10_000;
+
+fn dense(every: usize) -> Vec<f64> {
+    (0..DIM).map(|i| if i % every == 0 { i as f64 + 0.5 } else { 0.0 }).collect()
+}
+
+#[bench]
+fn from_dense_slice_10k(b: &mut Bencher) {
+    let x = dense(1);
+    b.iter(|| DataVec::from_dense_slice(test::black_box(&x)));
+}
+
+#[bench]
+fn from_dense_slice_10k_sparse(b: &mut Bencher) {
+    let x = dense(10);
+    b.iter(|| DataVec::from_dense_slice(test::black_box(&x)));
+}
+
+// What from_dense_slice did before counting the nonzeros first: grow the nodes from empty
+#[bench]
+fn grow_from_empty_10k(b: &mut Bencher) {
+    let x = dense(1);
+    b.iter(|| {
+        let mut v = Vec::new();
+        for (i, &x) in test::black_box(&x).iter().enumerate().filter(|&(_, &x)| x != 0.0) {
+            v.push(SvmNode((i + 1) as i32, x));
+        }
+        v.push(SvmNode(-1, 0.0));
+        v
+    });
+}
@@ -0,0 +1,21 @@
+#![feature(test)]
+extern crate test;
+extern crate rsvm;
+
+use rsvm::{DataVec,SvmProblem,SvmParameter};
+use test::Bencher;
+
+// Run once as is and once with `--features rayon`. Every grid point is cross validated on its own,
+// so the parallel search should get close to linearly faster with the number of cores.
+#[bench]
+fn grid_search_10x10(b: &mut Bencher) {
+    let prob = SvmProblem::from_pairs((0..200).map(|i| {
+        let x = vec![(i % 17) as f64 / 17.0, (i % 5) as f64 / 5.0];
+        (if x[0] + x[1] > 1.0 { 1.0 } else { -1.0 }, DataVec::from_dense(x))
+    }));
+    let param = SvmParameter::default_classification(None);
+    let cs: Vec<f64> = (-5..5).map(|e| 2f64.powi(e)).collect();
+    let gammas: Vec<f64> = (-5..5).map(|e| 2f64.powi(e)).collect();
+
+    b.iter(|| prob.grid_search(&param, &cs, &gammas, 5));
+}
@@ -0,0 +1,99 @@
+#![feature(test)]
+extern crate test;
+extern crate rsvm;
+
+use rsvm::{DataVec,SvmProblem,SvmParameter};
+use test::Bencher;
+
+use std::alloc::{GlobalAlloc,Layout,System};
+use std::cell::Cell;
+
+// Counts the allocations made on each thread, so the tests below can check how many a sweep
+// makes without the benches running in parallel with them getting in the way.
+struct CountingAlloc;
+
+thread_local!(static ALLOCATIONS: Cell<usize> = const { Cell::new(0) });
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|n| n.set(n.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+// The number of allocations f makes, not counting any its result makes when it's dropped
+fn allocations<R, F: FnOnce() -> R>(f: F) -> usize {
+    let before = ALLOCATIONS.with(|n| n.get());
+    let result = f();
+    let after = ALLOCATIONS.with(|n| n.get());
+    drop(result);
+
+    after - before
+}
+
+// A C-SVC weighting 100 classes, so the C representation caches weight vectors
+fn weighted_param() -> SvmParameter {
+    let flags: Vec<String> = (1..101).flat_map(|label| vec![format!("-w{}", label), "2".to_string()]).collect();
+    SvmParameter::from_command_line_args(&flags).unwrap()
+}
+
+// weighted_param after check_parameter has built its cached weight vectors
+fn converted_param() -> SvmParameter {
+    let param = weighted_param();
+    let prob = SvmProblem::from_pairs((1..101).map(|label| (label as f64, DataVec::from_dense(vec![label as f64]))));
+    let _ = prob.check_parameter(&param);
+
+    param
+}
+
+fn grid() -> Vec<f64> {
+    (-10..10).map(|e| 2f64.powi(e)).collect()
+}
+
+fn sweep(param: &SvmParameter) -> Vec<SvmParameter> {
+    let grid = grid();
+    let mut params = Vec::with_capacity(grid.len() * grid.len());
+    for &c in &grid {
+        for &gamma in &grid {
+            params.push(param.with_c(c).with_kernel_gamma(gamma));
+        }
+    }
+
+    params
+}
+
+#[test]
+fn sweep_copies_each_grid_point_once() {
+    let fresh = weighted_param();
+    let param = converted_param();
+    let one_copy = allocations(|| fresh.clone());
+
+    // The cached weight vectors aren't copied, so converting param doesn't make copying it
+    // (or sweeping it) any more expensive
+    assert_eq!(allocations(|| param.clone()), one_copy);
+    // One each for the grid and the list of parameters, then one copy of param per grid point
+    assert_eq!(allocations(|| sweep(&fresh)), 2 + 400 * one_copy);
+    assert_eq!(allocations(|| sweep(&param)), 2 + 400 * one_copy);
+}
+
+#[bench]
+fn sweep_20x20(b: &mut Bencher) {
+    let param = weighted_param();
+    b.iter(|| sweep(test::black_box(&param)));
+}
+
+// check_parameter builds the cached weight vectors. With clones leaving them behind this
+// should take the same time as sweep_20x20 (sweep_copies_each_grid_point_once counts the
+// allocations), copying them would make it slower.
+#[bench]
+fn sweep_20x20_after_conversion(b: &mut Bencher) {
+    let param = converted_param();
+    b.iter(|| sweep(test::black_box(&param)));
+}
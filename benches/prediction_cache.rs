@@ -0,0 +1,30 @@
+#![feature(test)]
+extern crate test;
+extern crate rsvm;
+
+use rsvm::{DataVec,SvmModel,SvmProblem,SvmParameter,PredictionCache};
+use test::Bencher;
+
+fn model() -> (SvmModel, DataVec) {
+    let x: Vec<DataVec> = (0..300).map(|i| DataVec::from_dense((0..20).map(|j| ((i * j) % 11) as f64).collect())).collect();
+    let y = (0..300).map(|i| (i % 3) as f64).collect();
+    let model = SvmProblem::new(x, y).unwrap()
+        .train(SvmParameter::default_classification(None)).unwrap();
+
+    (model, DataVec::from_dense((0..20).map(|j| (j % 4) as f64).collect()))
+}
+
+#[bench]
+fn predict_values(b: &mut Bencher) {
+    let (model, v) = model();
+    b.iter(|| model.predict_values(test::black_box(&v), None));
+}
+
+#[bench]
+fn cache_hit(b: &mut Bencher) {
+    let (model, v) = model();
+    let mut cache = PredictionCache::new(&model);
+    cache.predict_values(&v).unwrap();
+
+    b.iter(|| cache.predict_values(test::black_box(&v)).unwrap().0);
+}
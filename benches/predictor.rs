@@ -0,0 +1,37 @@
+#![feature(test)]
+extern crate test;
+extern crate rsvm;
+
+use rsvm::{DataVec,SvmModel,SvmProblem,SvmParameter,Predictor};
+use test::Bencher;
+
+// A 5 class model, so each prediction has 10 decision values to write
+fn model() -> (SvmModel, Vec<DataVec>) {
+    let x: Vec<DataVec> = (0..250).map(|i| DataVec::from_dense(vec![(i % 5) as f64, (i % 7) as f64 / 7.0])).collect();
+    let y = (0..250).map(|i| (i % 5) as f64).collect();
+    let model = SvmProblem::new(x.clone(), y).unwrap()
+        .train(SvmParameter::default_classification(None)).unwrap();
+
+    (model, x)
+}
+
+#[bench]
+fn predict_values_allocating(b: &mut Bencher) {
+    let (model, x) = model();
+    b.iter(|| {
+        for v in &x {
+            test::black_box(model.predict_values(v, None).unwrap());
+        }
+    });
+}
+
+#[bench]
+fn predict_values_with_predictor(b: &mut Bencher) {
+    let (model, x) = model();
+    let mut predictor = Predictor::new(&model);
+    b.iter(|| {
+        for v in &x {
+            test::black_box(predictor.predict_values(v).unwrap());
+        }
+    });
+}
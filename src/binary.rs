@@ -0,0 +1,107 @@
+use ::SvmNode;
+use ::datavec::DataVec;
+use ::prob::SvmProblem;
+
+use std::io::{self,Read,Write};
+
+// Identifies the format, with the last byte as its version
+const MAGIC: &[u8; 8] = b"RSVMBIN1";
+
+impl SvmProblem {
+    /// Writes the problem in a compact binary format that read_binary loads much faster than
+    /// an SVMLight file can be parsed, e.g. to cache a parsed dataset between runs.
+    ///
+    /// The format is little-endian throughout: the 8 bytes `RSVMBIN1`, the number of samples
+    /// as a u64, and a byte that's 1 if the samples have IDs (see with_ids) and 0 otherwise. Then
+    /// for each sample its label as an f64, its ID as a u64 if there are IDs, its number of
+    /// features as a u32, and each feature as an i32 index followed by an f64 value.
+    pub fn write_binary<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&(self.len() as u64).to_le_bytes())?;
+        writer.write_all(&[self.ids().is_some() as u8])?;
+
+        for (i, (label, v)) in self.iter().enumerate() {
+            writer.write_all(&label.to_le_bytes())?;
+            if let Some(ids) = self.ids() {
+                writer.write_all(&ids[i].to_le_bytes())?;
+            }
+
+            let features: Vec<&SvmNode> = v.iter().filter(|node| node.0 != -1).collect();
+            writer.write_all(&(features.len() as u32).to_le_bytes())?;
+            for &&SvmNode(idx, val) in &features {
+                writer.write_all(&idx.to_le_bytes())?;
+                writer.write_all(&val.to_le_bytes())?;
+            }
+        }
+
+        writer.flush()
+    }
+
+    /// Reads a problem written by write_binary. Returns an InvalidData error if the input isn't in
+    /// that format or has a feature index below 1 (other than the 0 at the front of a precomputed
+    /// kernel row), and an UnexpectedEof error if it's cut short.
+    ///
+    /// Reads are small, so wrap unbuffered readers such as files in a BufReader.
+    pub fn read_binary<R: Read>(mut reader: R) -> io::Result<SvmProblem> {
+        let mut magic = [0; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid("Not a binary problem file"));
+        }
+
+        let n = read_u64(&mut reader)? as usize;
+        let has_ids = match read_bytes::<_, [u8; 1]>(&mut reader)?[0] {
+            0 => false,
+            1 => true,
+            _ => { return Err(invalid("Bad ID flag")); },
+        };
+
+        // The count isn't trusted for preallocation, in case the file is corrupt
+        let cap = n.min(1 << 16);
+        let (mut x, mut y) = (Vec::with_capacity(cap), Vec::with_capacity(cap));
+        let mut ids = Vec::new();
+
+        for _ in 0..n {
+            y.push(f64::from_le_bytes(read_bytes(&mut reader)?));
+            if has_ids {
+                ids.push(read_u64(&mut reader)?);
+            }
+
+            let nnz = u32::from_le_bytes(read_bytes(&mut reader)?) as usize;
+            let mut features = Vec::with_capacity(nnz.min(1 << 16));
+            for _ in 0..nnz {
+                let idx = i32::from_le_bytes(read_bytes(&mut reader)?);
+                let val = f64::from_le_bytes(read_bytes(&mut reader)?);
+                // Except for a precomputed kernel row's leading serial number
+                if idx < 1 && !(idx == 0 && features.is_empty()) {
+                    return Err(invalid(&format!("Feature index {} is less than 1", idx)));
+                }
+
+                features.push((idx, val));
+            }
+
+            x.push(DataVec::from(features));
+        }
+
+        let prob = SvmProblem::new(x, y).map_err(|err| invalid(&err))?;
+        if has_ids {
+            prob.with_ids(ids).map_err(|err| invalid(&err))
+        } else {
+            Ok(prob)
+        }
+    }
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn read_u64<R: Read>(reader: &mut R) -> io::Result<u64> {
+    read_bytes(reader).map(u64::from_le_bytes)
+}
+
+fn read_bytes<R: Read, B: AsMut<[u8]> + Default>(reader: &mut R) -> io::Result<B> {
+    let mut buf = B::default();
+    reader.read_exact(buf.as_mut())?;
+    Ok(buf)
+}
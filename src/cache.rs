@@ -0,0 +1,68 @@
+use ::SvmNode;
+use ::datavec::DataVec;
+use ::model::{SvmModel,PredictError};
+
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+
+/// Memoizes the decision values of a model for repeated queries of the same vectors.
+/// The first query of a vector runs predict_values, and later queries with the same features
+/// are answered from the cache.
+///
+/// The cache borrows the model, so the model can't be changed (or dropped) while any cached
+/// values could be stale.
+pub struct PredictionCache<'a> {
+    model: &'a SvmModel,
+    cache: HashMap<Key, (f64, Vec<f64>)>,
+}
+
+impl<'a> PredictionCache<'a> {
+    /// Makes an empty cache for the model.
+    pub fn new(model: &'a SvmModel) -> PredictionCache<'a> {
+        PredictionCache {
+            model: model,
+            cache: HashMap::new(),
+        }
+    }
+
+    /// The same as SvmModel::predict_values, but only computed once for any given set
+    /// of features. Vectors predict_values rejects aren't cached.
+    pub fn predict_values(&mut self, test_vec: &DataVec) -> Result<(f64, &[f64]), PredictError> {
+        let model = self.model;
+        let &mut (y, ref dec_values) = match self.cache.entry(key(test_vec)) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(model.predict_values(test_vec, None)?),
+        };
+
+        Ok((y, dec_values))
+    }
+
+    /// The number of vectors cached.
+    pub fn len(&self) -> usize {
+        self.cache.len()
+    }
+
+    /// Whether nothing has been cached yet.
+    pub fn is_empty(&self) -> bool {
+        self.cache.is_empty()
+    }
+
+    /// Forgets every cached value.
+    pub fn clear(&mut self) {
+        self.cache.clear();
+    }
+}
+
+// Floats can't be hashed directly, so the key uses their bit patterns. Vectors are
+// compared by their features only, in sorted order, the same as DataVec's PartialEq.
+type Key = Vec<(i32, u64)>;
+
+fn key(v: &DataVec) -> Key {
+    let mut key: Key = v.iter()
+        .filter(|&&SvmNode(idx, _)| idx != -1)
+        .map(|&SvmNode(idx, val)| (idx, val.to_bits()))
+        .collect();
+
+    key.sort_by_key(|&(idx, _)| idx);
+    key
+}
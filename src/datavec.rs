@@ -1,7 +1,17 @@
 use ::SvmNode;
+use ::rng::SplitMix;
 
-use std::cmp::{Ordering};
-use std::ops::{Deref,DerefMut};
+use core::cmp::{Ordering};
+use core::ops::{Deref,DerefMut};
+use core::fmt;
+use core::error::Error;
+use core::str::FromStr;
+use alloc::vec::Vec;
+use alloc::string::String;
+#[cfg(feature="std")]
+use alloc::format;
+#[cfg(feature="std")]
+use std::collections::HashMap;
 
 /// A DataVec is a sparse representation of a vector (usually a feature vector, but
 /// possibly a support vector as well).
@@ -20,9 +30,16 @@ impl DataVec {
     /// If you want to filter it with a different threshold according to your data,
     /// build the sparse vector yourself and construct the DataVec with from_sparse.
     pub fn from_dense(x: Vec<f64>) -> DataVec {
-        let mut v = Vec::new();
+        DataVec::from_dense_slice(&x)
+    }
+
+    /// The same as from_dense, but borrows the dense vector instead of consuming it.
+    pub fn from_dense_slice(x: &[f64]) -> DataVec {
+        // Counting first means exactly one allocation, with room for the terminator
+        let nnz = x.iter().filter(|&&x| x != 0.0).count();
+        let mut v = Vec::with_capacity(nnz + 1);
 
-        for (i, x) in x.into_iter().enumerate() {
+        for (i, &x) in x.iter().enumerate() {
             // We shouldn't be in the business of determining what
             // threshold should be filtered out, so only strict 0.0 reps
             // are filtered.
@@ -49,7 +66,9 @@ impl DataVec {
     /// must be in ascending order. This function takes care of the terminal tuple and sorting for you (but it will not break if either criterion
     /// is met beforehand).
     ///
-    /// Malformed input (indices that are lower than 1, but not -1) will panic.
+    /// Malformed input (indices that are lower than 1, but not -1) will panic. The one exception is a
+    /// single 0 index, which sorts to the front: precomputed kernel rows (see PrecomputedKernel) keep
+    /// the sample's serial number there.
     pub fn from_sparse(mut x: Vec<SvmNode>) -> DataVec {
         DataVec::sort(&mut x);
 		DataVec {
@@ -58,9 +77,18 @@ impl DataVec {
 		}
 	}
 
+    /// The same as from_sparse, but copies the components out of a borrowed slice
+    /// instead of consuming a Vec.
+    pub fn from_sparse_slice(x: &[SvmNode]) -> DataVec {
+        DataVec::from_sparse(x.to_vec())
+    }
+
     /// Sorts the vector again. If the DataVec is ever modified (e.g. via DerefMut),
     /// this sorts it correctly again. This is automatically called by the SvmProb
     /// constructor you usually shouldn't need to worry about this.
+    ///
+    /// Like from_sparse this also makes sure there's exactly one terminator at the end, and
+    /// panics if any index is below 1 (other than a single 0).
     pub fn resort(&mut self) {
         if !self.is_sorted {
             DataVec::sort(self);
@@ -68,25 +96,402 @@ impl DataVec {
         }
     }
 
-    fn sort(x: &mut Vec<SvmNode>) {
-        // Sort by the index as in the libsvm docs
-        x.sort_by(|a, b| {
-            let (&SvmNode(idx1, _), &SvmNode(idx2, _)) = (a, b);
-
-            match (idx1, idx2) {
-                (-1, -1) => Ordering::Equal,
-                (-1, _) => Ordering::Greater,
-                (_, -1) => Ordering::Less,
-                (x, y) if x < 1 || y < 1
-                    => { panic!("Index is less than 1 but not -1. a: {:?}, b: {:?}", a, b) },
-                (x, y) => x.cmp(&y),
+    /// Takes the nodes out of the vector, including the -1 terminator at the end, in the layout
+    /// libsvm expects. The vector is resorted first if it was modified through DerefMut.
+    pub fn into_inner(mut self) -> Vec<SvmNode> {
+        self.resort();
+        self.v
+    }
+
+    /// The nodes as they're stored, including the -1 terminator. Unless the vector was modified
+    /// through DerefMut since it was last sorted, this is the layout libsvm expects.
+    pub fn as_nodes(&self) -> &[SvmNode] {
+        &self.v
+    }
+
+    /// Checks the invariants libsvm relies on, without fixing anything: every index is at
+    /// least 1, the indices strictly ascend, and the vector ends in its only -1 terminator. The first
+    /// index may also be 0, as in a precomputed kernel row.
+    /// The constructors always establish these, but mutating through DerefMut can break them
+    /// until the next resort.
+    pub fn validate(&self) -> Result<(), DataVecError> {
+        let features = match self.v.split_last() {
+            Some((&SvmNode(-1, _), features)) => features,
+            _ => { return Err(DataVecError::MisplacedTerminator); },
+        };
+
+        let mut prev = 0;
+        for (position, &SvmNode(idx, _)) in features.iter().enumerate() {
+            if idx == -1 {
+                return Err(DataVecError::MisplacedTerminator);
+            }
+            if idx == 0 && position == 0 {
+                continue;
+            }
+            if idx < 1 {
+                return Err(DataVecError::BadIndex{position: position, index: idx});
+            }
+            if idx <= prev {
+                return Err(DataVecError::Unsorted{position: position});
+            }
+
+            prev = idx;
+        }
+
+        Ok(())
+    }
+
+    /// Checks that every feature value is finite. libsvm doesn't check, and a NaN or infinite
+    /// value just makes every kernel value involving the vector (and so the model or prediction)
+    /// garbage. The position is that of the first bad feature, not counting the terminator.
+    pub fn validate_finite(&self) -> Result<(), DataVecError> {
+        match self.v.iter().filter(|node| node.0 != -1).position(|node| !node.1.is_finite()) {
+            Some(position) => Err(DataVecError::NonFinite{position: position}),
+            None => Ok(()),
+        }
+    }
+
+    /// Inserts a feature in its sorted position (before the terminator), so the vector
+    /// never needs to be resorted. If the index is already present, its value is replaced.
+    ///
+    /// Panics if index is less than 1.
+    pub fn append_feature(&mut self, index: i32, value: f64) {
+        assert!(index >= 1, "Feature index must be at least 1, got {}", index);
+        self.resort();
+
+        // The terminator is always last, so only search the features before it
+        let features = self.v.len() - 1;
+        match self.v[..features].binary_search_by(|&SvmNode(idx, _)| idx.cmp(&index)) {
+            Ok(pos) => { self.v[pos].1 = value; },
+            Err(pos) => { self.v.insert(pos, SvmNode(index, value)); },
+        }
+    }
+
+    /// Combines the features of both vectors into a new one. Any index present in both
+    /// has the two values summed.
+    pub fn merge(&self, other: &DataVec) -> DataVec {
+        self.merge_with_offset(other, 0)
+    }
+
+    /// The same as merge, except other's indices are all shifted up by offset first. This lets
+    /// two independently indexed vectors be concatenated without colliding, e.g. by using the
+    /// first vector's highest index as the offset.
+    ///
+    /// Panics if the offset brings any of other's indices below 1.
+    pub fn merge_with_offset(&self, other: &DataVec, offset: i32) -> DataVec {
+        let features = |v: &DataVec, offset: i32| -> Vec<SvmNode> {
+            v.v.iter()
+                .filter(|&&SvmNode(idx, _)| idx != -1)
+                .map(|&SvmNode(idx, val)| SvmNode(idx + offset, val))
+                .collect()
+        };
+
+        // Checked here since an index shifted to exactly -1 would otherwise pass for a terminator
+        let shifted = features(other, offset);
+        assert!(shifted.iter().all(|&SvmNode(idx, _)| idx >= 1), "Offset {} shifts a feature index below 1", offset);
+
+        let mut nodes = features(self, 0);
+        nodes.extend(shifted);
+
+        DataVec::from_colliding(nodes)
+    }
+
+    /// Rewrites each feature's index through mapping, e.g. to line up datasets built with
+    /// different feature vocabularies. Features that map to the same index are summed.
+    ///
+    /// Features with no entry in mapping are dropped if drop_unmapped is true, otherwise an error
+    /// is returned. An error is also returned if anything maps to an index below 1. On error
+    /// the vector is left unchanged.
+    #[cfg(feature="std")]
+    pub fn remap_indices(&mut self, mapping: &HashMap<i32, i32>, drop_unmapped: bool) -> Result<(), String> {
+        let mut nodes = Vec::with_capacity(self.v.len());
+
+        for &SvmNode(idx, val) in self.v.iter().filter(|&&SvmNode(idx, _)| idx != -1) {
+            match mapping.get(&idx) {
+                Some(&new_idx) if new_idx < 1 => {
+                    return Err(format!("Feature {} is mapped to {}, but indices must be at least 1", idx, new_idx));
+                },
+                Some(&new_idx) => nodes.push(SvmNode(new_idx, val)),
+                None if drop_unmapped => {},
+                None => { return Err(format!("Feature {} has no mapping", idx)); },
+            }
+        }
+
+        *self = DataVec::from_colliding(nodes);
+        Ok(())
+    }
+
+    /// Applies the hashing trick, collapsing the feature indices into 1..=num_buckets
+    /// and summing the features that collide. The hash is fixed, so the same index always
+    /// lands in the same bucket, which lets vectors be hashed independently (e.g. while streaming)
+    /// without knowing the full vocabulary up front.
+    ///
+    /// Panics if num_buckets is less than 1.
+    pub fn hash_features(&self, num_buckets: i32) -> DataVec {
+        assert!(num_buckets >= 1, "Number of buckets must be at least 1, got {}", num_buckets);
+
+        let nodes = self.v.iter()
+            .filter(|&&SvmNode(idx, _)| idx != -1)
+            .map(|&SvmNode(idx, val)| {
+                let hash = SplitMix::new(idx as u64).next_u64();
+                SvmNode((hash % num_buckets as u64) as i32 + 1, val)
+            })
+            .collect();
+
+        DataVec::from_colliding(nodes)
+    }
+
+    /// Sorts and terminates nodes, summing any that share an index.
+    fn from_colliding(mut nodes: Vec<SvmNode>) -> DataVec {
+        DataVec::sort(&mut nodes);
+
+        let mut merged: Vec<SvmNode> = Vec::with_capacity(nodes.len());
+        for node in nodes {
+            match merged.last_mut() {
+                Some(last) if last.0 == node.0 && node.0 != -1 => { last.1 += node.1; },
+                _ => merged.push(node),
+            }
+        }
+
+        DataVec { v: merged, is_sorted: true }
+    }
+
+    /// The fraction of features up to the highest index that are actually stored, e.g. 0.5
+    /// for a vector with features 2 and 4. A vector with no features has a density of 0.0.
+    pub fn density(&self) -> f64 {
+        let features = self.v.iter().filter(|&&SvmNode(idx, _)| idx != -1);
+        let (count, max_index) = features.fold((0, 0), |(count, max), &SvmNode(idx, _)| (count + 1, max.max(idx)));
+
+        if max_index == 0 {
+            0.0
+        } else {
+            count as f64 / max_index as f64
+        }
+    }
+
+    /// Replaces every feature value x with f(x), e.g. to log-scale or clip features. Unlike
+    /// going through DerefMut this skips the terminator, and since the indices are untouched
+    /// the vector doesn't need resorting afterwards.
+    ///
+    /// A value mapped to 0.0 is kept as an explicit zero.
+    pub fn map_values<F: FnMut(f64) -> f64>(&mut self, mut f: F) {
+        for node in self.v.iter_mut().filter(|node| node.0 != -1) {
+            node.1 = f(node.1);
+        }
+    }
+
+    /// Caps every feature value into [min, max], skipping the terminator.
+    ///
+    /// If the window has 0 as an end (e.g. [0, 1]) a value can be clamped to exactly 0.0, and
+    /// such nodes are dropped rather than kept as explicit zeros, since they say nothing a missing
+    /// feature doesn't. Explicit zeros that were already in the vector are kept.
+    ///
+    /// Panics if min is greater than max or either is NaN, like f64::clamp.
+    pub fn clamp_values(&mut self, min: f64, max: f64) {
+        self.v.retain_mut(|node| {
+            if node.0 == -1 {
+                return true;
             }
+
+            let clamped = node.1.clamp(min, max);
+            let keep = clamped != 0.0 || node.1 == 0.0;
+            node.1 = clamped;
+            keep
         });
-        let SvmNode(idx, _) = x[x.len() - 1];
+    }
+
+    /// A copy of the vector with clamp_values applied.
+    pub fn clamped(&self, min: f64, max: f64) -> DataVec {
+        let mut v = self.clone();
+        v.clamp_values(min, max);
+        v
+    }
+
+    /// Scales the vector in place so its L2 (Euclidean) norm is 1. An all-zero vector is left alone.
+    /// The square root needs the std feature.
+    #[cfg(feature="std")]
+    pub fn normalize_l2(&mut self) {
+        let norm = self.dot(self).sqrt();
+        self.scale(norm);
+    }
+
+    /// Scales the vector in place so its L1 norm (the sum of the absolute values) is 1.
+    /// An all-zero vector is left alone.
+    pub fn normalize_l1(&mut self) {
+        let norm = self.v.iter()
+            .filter(|&&SvmNode(idx, _)| idx != -1)
+            .map(|&SvmNode(_, val)| val.abs())
+            .sum();
+        self.scale(norm);
+    }
+
+    /// A copy of the vector normalized with normalize_l2.
+    #[cfg(feature="std")]
+    pub fn normalized_l2(&self) -> DataVec {
+        let mut v = self.clone();
+        v.normalize_l2();
+        v
+    }
+
+    /// A copy of the vector normalized with normalize_l1.
+    pub fn normalized_l1(&self) -> DataVec {
+        let mut v = self.clone();
+        v.normalize_l1();
+        v
+    }
+
+    fn scale(&mut self, norm: f64) {
+        if norm == 0.0 {
+            return;
+        }
+
+        self.map_values(|x| x / norm);
+    }
+
+    /// Removes every feature with an index above max_index, e.g. to line a vector up
+    /// with a model trained on fewer features.
+    pub fn truncate_to_features(&mut self, max_index: i32) {
+        self.retain_features(|idx| idx <= max_index);
+    }
+
+    /// Keeps only the features whose index satisfies pred. The vector is resorted
+    /// and terminated afterwards, so it's always valid to hand to libsvm.
+    pub fn retain_features<F: Fn(i32) -> bool>(&mut self, pred: F) {
+        self.v.retain(|&SvmNode(idx, _)| idx == -1 || pred(idx));
+        self.is_sorted = false;
+        self.resort();
+    }
+
+    /// Computes the sparse dot product of two DataVecs by walking both index lists
+    /// in order. Both vectors must be sorted (see resort) for this to be correct; any
+    /// SvmNode at or after the -1 terminator is ignored.
+    pub fn dot(&self, other: &DataVec) -> f64 {
+        let (mut i, mut j) = (0, 0);
+        let mut sum = 0.0;
+
+        while i < self.v.len() && j < other.v.len() {
+            let (SvmNode(idx1, val1), SvmNode(idx2, val2)) = (self.v[i], other.v[j]);
+
+            if idx1 == -1 || idx2 == -1 {
+                break;
+            }
+
+            match idx1.cmp(&idx2) {
+                Ordering::Equal => {
+                    sum += val1 * val2;
+                    i += 1;
+                    j += 1;
+                },
+                Ordering::Less => { i += 1; },
+                Ordering::Greater => { j += 1; },
+            }
+        }
+
+        sum
+    }
+
+    fn sort(x: &mut Vec<SvmNode>) {
+        // Any terminators are stripped and a single one re-added at the end, so a vector whose
+        // terminator was removed (or duplicated) through DerefMut comes out valid. An empty vector
+        // is a legitimate all-zero feature vector, it just needs the terminator.
+        x.retain(|&SvmNode(idx, _)| idx != -1);
+        if let Some(node) = x.iter().find(|&&SvmNode(idx, _)| idx < 0) {
+            panic!("Index is less than 1 but not -1: {:?}", node);
+        }
+
+        // Sort by the index as in the libsvm docs. That puts a precomputed kernel row's 0 index
+        // first, where it belongs, but there can only be one.
+        x.sort_by_key(|&SvmNode(idx, _)| idx);
+        if x.len() > 1 && x[1].0 == 0 {
+            panic!("Index is less than 1 but not -1: {:?}", x[1]);
+        }
+        x.push(SvmNode(-1, 0.0));
+    }
+}
+
+pub mod protected {
+    use super::{DataVec,ParseDataVecError};
+    use ::SvmNode;
+    use alloc::vec::Vec;
+    use alloc::string::ToString;
+
+    /// Parses `index:value` tokens, keeping the byte offset into s of each token. On failure,
+    /// the offset of the offending part of the token (the index or value if that's what's malformed)
+    /// is returned with the error.
+    pub fn parse_features(s: &str) -> Result<Vec<(usize, SvmNode)>, (usize, ParseDataVecError)> {
+        let mut v = Vec::new();
+
+        for tok in s.split_whitespace() {
+            let offset = tok.as_ptr() as usize - s.as_ptr() as usize;
+
+            let colon = match tok.find(':') {
+                Some(colon) => colon,
+                None => { return Err((offset, ParseDataVecError::MissingColon(tok.to_string()))); },
+            };
+            let (idx, val) = (&tok[..colon], &tok[colon + 1..]);
+
+            let idx = match idx.parse::<i32>() {
+                Ok(idx) if idx >= 1 => idx,
+                _ => { return Err((offset, ParseDataVecError::BadIndex(tok.to_string()))); },
+            };
+
+            let val = match val.parse::<f64>() {
+                Ok(val) => val,
+                Err(_) => { return Err((offset + colon + 1, ParseDataVecError::BadValue(tok.to_string()))); },
+            };
+
+            v.push((offset, SvmNode(idx, val)));
+        }
+
+        Ok(v)
+    }
+
+    /// Wraps nodes that are already sorted and terminated without checking them or
+    /// copying them into a new allocation.
+    pub fn from_raw(v: Vec<SvmNode>) -> DataVec {
+        DataVec { v: v, is_sorted: true }
+    }
+}
+
+/// Two DataVecs are equal if they have the same (index, value) features, regardless of
+/// whether either has been resorted or terminated since it was modified.
+impl PartialEq for DataVec {
+    fn eq(&self, other: &DataVec) -> bool {
+        fn features(v: &DataVec) -> Vec<(i32, f64)> {
+            let mut features: Vec<(i32, f64)> = v.v.iter()
+                .filter(|&&SvmNode(idx, _)| idx != -1)
+                .map(|&SvmNode(idx, val)| (idx, val))
+                .collect();
 
-        if idx != -1 {
-            x.push(SvmNode(-1, 0.0));
+            if !v.is_sorted {
+                features.sort_by_key(|&(idx, _)| idx);
+            }
+
+            features
         }
+
+        features(self) == features(other)
+    }
+}
+
+/// The same as DataVec::from_dense.
+impl From<Vec<f64>> for DataVec {
+    fn from(x: Vec<f64>) -> DataVec {
+        DataVec::from_dense(x)
+    }
+}
+
+/// Treats each tuple as an (index, value) pair, the same as DataVec::from_sparse.
+impl From<Vec<(i32, f64)>> for DataVec {
+    fn from(x: Vec<(i32, f64)>) -> DataVec {
+        DataVec::from(&x[..])
+    }
+}
+
+/// Treats each tuple as an (index, value) pair, the same as DataVec::from_sparse.
+impl<'a> From<&'a [(i32, f64)]> for DataVec {
+    fn from(x: &'a [(i32, f64)]) -> DataVec {
+        DataVec::from_sparse(x.iter().map(|&(idx, val)| SvmNode(idx, val)).collect())
     }
 }
 
@@ -103,4 +508,88 @@ impl DerefMut for DataVec {
         self.is_sorted = false;
         &mut self.v
     }
-}
\ No newline at end of file
+}
+
+/// A broken DataVec invariant, see DataVec::validate.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DataVecError {
+    /// The feature at position has an index below 1 that isn't a terminator (or a 0 at the front).
+    BadIndex{position: usize, index: i32},
+    /// The feature at position doesn't have a higher index than the one before it.
+    Unsorted{position: usize},
+    /// The vector doesn't end with a -1 terminator, or has one before the end.
+    MisplacedTerminator,
+    /// The feature at position has a NaN or infinite value.
+    NonFinite{position: usize},
+}
+
+impl fmt::Display for DataVecError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            DataVecError::BadIndex{position, index} => write!(f, "feature {} has index {}, which is below 1", position, index),
+            DataVecError::Unsorted{position} => write!(f, "feature {} is out of order", position),
+            DataVecError::MisplacedTerminator => write!(f, "the vector isn't terminated by a single -1 at the end"),
+            DataVecError::NonFinite{position} => write!(f, "feature {} is NaN or infinite", position),
+        }
+    }
+}
+
+impl Error for DataVecError {}
+
+/// Formats the vector as the feature portion of a line in libsvm's data format,
+/// i.e. `index:value` pairs separated by spaces (e.g. `3:0.5 7:1.2`). The -1 terminator
+/// is not printed.
+impl fmt::Display for DataVec {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut first = true;
+
+        for &SvmNode(idx, val) in self.v.iter().take_while(|&&SvmNode(idx, _)| idx != -1) {
+            if !first {
+                write!(f, " ")?;
+            }
+            first = false;
+
+            write!(f, "{}:{}", idx, val)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The reasons a string can fail to parse as a DataVec. Each variant holds the
+/// offending `index:value` token.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParseDataVecError {
+    /// The token has no colon separating the index and value.
+    MissingColon(String),
+    /// The index isn't an integer, or is less than 1.
+    BadIndex(String),
+    /// The value isn't a number.
+    BadValue(String),
+}
+
+impl fmt::Display for ParseDataVecError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ParseDataVecError::MissingColon(ref tok) => write!(f, "Feature \"{}\" is missing a colon", tok),
+            ParseDataVecError::BadIndex(ref tok) => write!(f, "Feature \"{}\" does not have an index of at least 1", tok),
+            ParseDataVecError::BadValue(ref tok) => write!(f, "Feature \"{}\" does not have a numeric value", tok),
+        }
+    }
+}
+
+impl Error for ParseDataVecError {}
+
+/// Parses the feature portion of a line in libsvm's data format, e.g. `1:0.3 4:9.2`.
+/// Tokens are separated by whitespace, and the result is sorted and terminated as with
+/// from_sparse.
+impl FromStr for DataVec {
+    type Err = ParseDataVecError;
+
+    fn from_str(s: &str) -> Result<DataVec, ParseDataVecError> {
+        match protected::parse_features(s) {
+            Ok(features) => Ok(DataVec::from_sparse(features.into_iter().map(|(_, node)| node).collect())),
+            Err((_, err)) => Err(err),
+        }
+    }
+}
@@ -0,0 +1,183 @@
+use std::convert::TryFrom;
+
+use ::param::{KernelParam,SvmType,KernelType};
+use ::datavec::DataVec;
+use ::SvmNode;
+
+use ::rustc_serialize::{Encodable,Decodable,Encoder,Decoder};
+
+/// A plain copy of everything that makes up a trained model's decision function,
+/// see SvmModel::to_model_dump. Unlike SvmModel this doesn't reference any libsvm memory,
+/// and it encodes as an ordinary struct (so e.g. as a JSON object) rather than as libsvm's model file.
+#[derive(Clone, Debug)]
+pub struct ModelDump {
+    pub svm_type: SvmType,
+    pub kernel_param: KernelParam,
+    /// The class labels, empty for regression and one-class models.
+    pub labels: Vec<i32>,
+    /// The number of support vectors of each class, empty for regression and one-class models.
+    pub n_sv: Vec<i32>,
+    /// The support vectors, grouped by class in the same order as labels.
+    pub support_vectors: Vec<DataVec>,
+    /// The nr_class-1 rows of coefficients, each with one entry per support vector.
+    pub sv_coef: Vec<Vec<f64>>,
+    /// The constant term of each decision function.
+    pub rho: Vec<f64>,
+}
+
+impl ModelDump {
+    /// Predicts a vector the same way libsvm's svm_predict does, but entirely in Rust using
+    /// KernelParam::evaluate, so it works anywhere a dump can be loaded. This should agree with
+    /// SvmModel::predict up to floating point error.
+    ///
+    /// Precomputed kernels can't be evaluated this way, so this panics for them.
+    pub fn predict(&self, x: &DataVec) -> f64 {
+        self.predict_values(x).0
+    }
+
+    /// The pure Rust counterpart of SvmModel::predict_values, returning the prediction along
+    /// with the decision value of each decision function.
+    pub fn predict_values(&self, x: &DataVec) -> (f64, Vec<f64>) {
+        let kvalues: Vec<f64> = self.support_vectors.iter()
+            .map(|sv| self.kernel_param.evaluate(x, sv))
+            .collect();
+
+        match self.svm_type {
+            SvmType::OneClass | SvmType::EpsilonSvr | SvmType::NuSvr => {
+                let sum: f64 = self.sv_coef[0].iter().zip(&kvalues).map(|(coef, k)| coef * k).sum();
+                let dec = sum - self.rho[0];
+
+                let y = match self.svm_type {
+                    SvmType::OneClass => if dec > 0.0 { 1.0 } else { -1.0 },
+                    _ => dec,
+                };
+
+                (y, vec![dec])
+            },
+            SvmType::CSvc | SvmType::NuSvc => self.predict_classification(&kvalues),
+        }
+    }
+
+    // One-vs-one voting, as in svm_predict_values. The support vectors of class i start at
+    // start[i], and for the pair (i, j) class i's coefficients are in row j-1 while class j's
+    // are in row i.
+    fn predict_classification(&self, kvalues: &[f64]) -> (f64, Vec<f64>) {
+        let nr_class = self.labels.len();
+
+        let mut start = vec![0; nr_class];
+        for i in 1..nr_class {
+            start[i] = start[i - 1] + self.n_sv[i - 1] as usize;
+        }
+
+        let mut votes = vec![0; nr_class];
+        let mut dec_values = Vec::with_capacity(self.rho.len());
+
+        for i in 0..nr_class {
+            for j in i+1..nr_class {
+                let (si, ni) = (start[i], self.n_sv[i] as usize);
+                let (sj, nj) = (start[j], self.n_sv[j] as usize);
+
+                let sum_i: f64 = (si..si + ni).map(|k| self.sv_coef[j - 1][k] * kvalues[k]).sum();
+                let sum_j: f64 = (sj..sj + nj).map(|k| self.sv_coef[i][k] * kvalues[k]).sum();
+                let dec = sum_i + sum_j - self.rho[dec_values.len()];
+
+                if dec > 0.0 {
+                    votes[i] += 1;
+                } else {
+                    votes[j] += 1;
+                }
+                dec_values.push(dec);
+            }
+        }
+
+        // Ties go to the earliest label, like libsvm
+        let mut winner = 0;
+        for i in 1..nr_class {
+            if votes[i] > votes[winner] {
+                winner = i;
+            }
+        }
+
+        (self.labels[winner] as f64, dec_values)
+    }
+}
+
+impl Encodable for ModelDump {
+    fn encode<S: Encoder>(&self, s: &mut S) -> Result<(), S::Error> {
+        let (kernel_type, degree, gamma, coef0) = kernel_fields(&self.kernel_param);
+        let support_vectors: Vec<Vec<(i32, f64)>> = self.support_vectors.iter()
+            .map(|v| v.iter().map(|&SvmNode(idx, val)| (idx, val)).collect())
+            .collect();
+
+        s.emit_struct("ModelDump", 10, |s| {
+            s.emit_struct_field("svm_type", 0, |s| (self.svm_type as i32).encode(s))?;
+            s.emit_struct_field("kernel_type", 1, |s| (kernel_type as i32).encode(s))?;
+            s.emit_struct_field("degree", 2, |s| degree.encode(s))?;
+            s.emit_struct_field("gamma", 3, |s| gamma.encode(s))?;
+            s.emit_struct_field("coef0", 4, |s| coef0.encode(s))?;
+            s.emit_struct_field("labels", 5, |s| self.labels.encode(s))?;
+            s.emit_struct_field("n_sv", 6, |s| self.n_sv.encode(s))?;
+            s.emit_struct_field("support_vectors", 7, |s| support_vectors.encode(s))?;
+            s.emit_struct_field("sv_coef", 8, |s| self.sv_coef.encode(s))?;
+            s.emit_struct_field("rho", 9, |s| self.rho.encode(s))
+        })
+    }
+}
+
+impl Decodable for ModelDump {
+    fn decode<D: Decoder>(d: &mut D) -> Result<Self, D::Error> {
+        d.read_struct("ModelDump", 10, |d| {
+            let svm_type = d.read_struct_field("svm_type", 0, i32::decode)?;
+            let kernel_type = d.read_struct_field("kernel_type", 1, i32::decode)?;
+            let degree = d.read_struct_field("degree", 2, i32::decode)?;
+            let gamma = d.read_struct_field("gamma", 3, f64::decode)?;
+            let coef0 = d.read_struct_field("coef0", 4, f64::decode)?;
+            let labels = d.read_struct_field("labels", 5, Vec::<i32>::decode)?;
+            let n_sv = d.read_struct_field("n_sv", 6, Vec::<i32>::decode)?;
+            let support_vectors = d.read_struct_field("support_vectors", 7, Vec::<Vec<(i32, f64)>>::decode)?;
+            let sv_coef = d.read_struct_field("sv_coef", 8, Vec::<Vec<f64>>::decode)?;
+            let rho = d.read_struct_field("rho", 9, Vec::<f64>::decode)?;
+
+            let svm_type = match SvmType::try_from(svm_type) {
+                Err(err) => { return Err(d.error(&err)); },
+                Ok(svm_type) => svm_type,
+            };
+            let kernel_param = match KernelType::try_from(kernel_type) {
+                Err(err) => { return Err(d.error(&err)); },
+                Ok(kernel_type) => make_kernel_param(kernel_type, degree, gamma, coef0),
+            };
+
+            Ok(ModelDump {
+                svm_type: svm_type,
+                kernel_param: kernel_param,
+                labels: labels,
+                n_sv: n_sv,
+                support_vectors: support_vectors.into_iter().map(DataVec::from).collect(),
+                sv_coef: sv_coef,
+                rho: rho,
+            })
+        })
+    }
+}
+
+// The kernel is stored flattened the same way svm_parameter stores it, with unused fields as 0.
+fn kernel_fields(kernel_param: &KernelParam) -> (KernelType, i32, f64, f64) {
+    use KernelParam::*;
+    match *kernel_param {
+        Linear => (KernelType::Linear, 0, 0.0, 0.0),
+        Poly{degree, gamma, coef0} => (KernelType::Poly, degree, gamma, coef0),
+        Rbf{gamma} => (KernelType::Rbf, 0, gamma, 0.0),
+        Sigmoid{gamma, coef0} => (KernelType::Sigmoid, 0, gamma, coef0),
+        Precomputed => (KernelType::Precomputed, 0, 0.0, 0.0),
+    }
+}
+
+fn make_kernel_param(kernel_type: KernelType, degree: i32, gamma: f64, coef0: f64) -> KernelParam {
+    match kernel_type {
+        KernelType::Linear => KernelParam::Linear,
+        KernelType::Poly => KernelParam::Poly{degree: degree, gamma: gamma, coef0: coef0},
+        KernelType::Rbf => KernelParam::Rbf{gamma: gamma},
+        KernelType::Sigmoid => KernelParam::Sigmoid{gamma: gamma, coef0: coef0},
+        KernelType::Precomputed => KernelParam::Precomputed,
+    }
+}
@@ -0,0 +1,73 @@
+use ::datavec::DataVec;
+use ::prob::{SvmProblem,TrainError};
+use ::param::SvmParameter;
+use ::model::SvmModel;
+use ::rng::SplitMix;
+
+/// A bagging ensemble: several models, each trained on a bootstrap sample of the same problem,
+/// whose predictions are combined.
+pub struct SvmEnsemble {
+    models: Vec<SvmModel>,
+}
+
+impl SvmEnsemble {
+    /// Trains n_estimators models with param, each on a bootstrap sample (as many samples
+    /// as prob has, drawn with replacement) chosen deterministically from seed.
+    ///
+    /// Returns an error if n_estimators is 0, prob is empty, or any of the models fails to train.
+    pub fn bagging(prob: &SvmProblem, param: &SvmParameter, n_estimators: usize, seed: u64)
+                   -> Result<SvmEnsemble, TrainError> {
+        if n_estimators == 0 {
+            return Err(TrainError::InvalidParameter("An ensemble needs at least one estimator".to_string()));
+        }
+        if prob.is_empty() {
+            return Err(TrainError::InvalidParameter("Cannot bootstrap an empty problem".to_string()));
+        }
+
+        let mut rng = SplitMix::new(seed);
+        let mut models = Vec::with_capacity(n_estimators);
+
+        for _ in 0..n_estimators {
+            let indices: Vec<usize> = (0..prob.len()).map(|_| rng.below(prob.len())).collect();
+
+            match prob.subset(&indices).train(param.clone()) {
+                Ok(model) => models.push(model),
+                Err(err) => { return Err(err); },
+            }
+        }
+
+        Ok(SvmEnsemble { models: models })
+    }
+
+    /// The models making up the ensemble.
+    pub fn models(&self) -> &[SvmModel] {
+        &self.models
+    }
+
+    /// Predicts with every model and combines the results: the mean for regression models,
+    /// and otherwise a majority vote (ties going to the label predicted first).
+    pub fn predict(&self, x: &DataVec) -> f64 {
+        let predictions = self.models.iter().map(|model| model.predict(x));
+
+        if self.models[0].is_regression() {
+            return predictions.sum::<f64>() / self.models.len() as f64;
+        }
+
+        let mut votes: Vec<(f64, usize)> = Vec::new();
+        for y in predictions {
+            match votes.iter_mut().position(|&mut (label, _)| label == y) {
+                Some(pos) => votes[pos].1 += 1,
+                None => votes.push((y, 1)),
+            }
+        }
+
+        let mut winner = votes[0];
+        for &vote in &votes[1..] {
+            if vote.1 > winner.1 {
+                winner = vote;
+            }
+        }
+
+        winner.0
+    }
+}
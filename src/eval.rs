@@ -0,0 +1,190 @@
+#[cfg(feature="std")]
+use core::cmp::Ordering;
+use alloc::vec::Vec;
+use alloc::string::String;
+use alloc::{format,vec};
+
+use ::label::Label;
+
+/// Tallies of predicted versus true labels for a classification model. Rows are the
+/// true labels and columns the predicted ones, both in the order of labels().
+#[derive(Clone, Debug)]
+pub struct ConfusionMatrix {
+    labels: Vec<i32>,
+    counts: Vec<Vec<usize>>,
+}
+
+impl ConfusionMatrix {
+    /// Makes an empty matrix over the given labels.
+    pub fn new(labels: Vec<i32>) -> ConfusionMatrix {
+        let n = labels.len();
+
+        ConfusionMatrix {
+            labels: labels,
+            counts: vec![vec![0; n]; n],
+        }
+    }
+
+    /// Records one prediction. Labels that the matrix doesn't have yet are added to the end.
+    pub fn add(&mut self, actual: i32, predicted: i32) {
+        let i = self.index_or_insert(actual);
+        let j = self.index_or_insert(predicted);
+
+        self.counts[i][j] += 1;
+    }
+
+    /// The same as add, but for typed labels. Returns an error rather than recording anything
+    /// if either is a regression target, since those aren't classes.
+    pub fn add_labels(&mut self, actual: Label, predicted: Label) -> Result<(), String> {
+        match (actual.class(), predicted.class()) {
+            (Some(actual), Some(predicted)) => {
+                self.add(actual, predicted);
+                Ok(())
+            },
+            _ => Err(format!("Can't tally regression targets as classes: {} predicted as {}", actual, predicted)),
+        }
+    }
+
+    /// The labels indexing the rows and columns.
+    pub fn labels(&self) -> &[i32] {
+        &self.labels
+    }
+
+    /// The number of samples with the true label actual that were predicted as predicted.
+    pub fn count(&self, actual: i32, predicted: i32) -> usize {
+        match (self.index(actual), self.index(predicted)) {
+            (Some(i), Some(j)) => self.counts[i][j],
+            _ => 0,
+        }
+    }
+
+    /// The total number of recorded predictions.
+    pub fn total(&self) -> usize {
+        self.counts.iter().map(|row| row.iter().sum::<usize>()).sum()
+    }
+
+    /// The fraction of all predictions that were correct, or 0.0 if there are none.
+    pub fn accuracy(&self) -> f64 {
+        let correct: usize = (0..self.labels.len()).map(|i| self.counts[i][i]).sum();
+        ratio(correct, self.total())
+    }
+
+    /// Of the samples predicted as label, the fraction that really were label.
+    /// This is 0.0 if nothing was predicted as label.
+    pub fn precision(&self, label: i32) -> f64 {
+        match self.index(label) {
+            Some(j) => ratio(self.counts[j][j], self.counts.iter().map(|row| row[j]).sum()),
+            None => 0.0,
+        }
+    }
+
+    /// Of the samples that really were label, the fraction predicted as label.
+    /// This is 0.0 if no sample was label.
+    pub fn recall(&self, label: i32) -> f64 {
+        match self.index(label) {
+            Some(i) => ratio(self.counts[i][i], self.counts[i].iter().sum()),
+            None => 0.0,
+        }
+    }
+
+    /// The harmonic mean of precision and recall for label, or 0.0 if both are 0.
+    pub fn f1(&self, label: i32) -> f64 {
+        let (p, r) = (self.precision(label), self.recall(label));
+
+        if p + r == 0.0 {
+            0.0
+        } else {
+            2.0 * p * r / (p + r)
+        }
+    }
+
+    fn index(&self, label: i32) -> Option<usize> {
+        self.labels.iter().position(|&l| l == label)
+    }
+
+    fn index_or_insert(&mut self, label: i32) -> usize {
+        if let Some(i) = self.index(label) {
+            return i;
+        }
+
+        self.labels.push(label);
+        for row in &mut self.counts {
+            row.push(0);
+        }
+        self.counts.push(vec![0; self.labels.len()]);
+
+        self.labels.len() - 1
+    }
+}
+
+fn ratio(num: usize, denom: usize) -> f64 {
+    if denom == 0 {
+        0.0
+    } else {
+        num as f64 / denom as f64
+    }
+}
+
+/// The points (false positive rate, true positive rate) of the ROC curve for scored
+/// samples, given as (score, is_positive) where a higher score means more likely positive.
+/// The curve starts at (0, 0) and has one point per distinct score, ending at (1, 1).
+#[cfg(feature="std")]
+pub fn roc_curve(scored: &[(f64, bool)]) -> Vec<(f64, f64)> {
+    let mut sorted = scored.to_vec();
+    sorted.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(Ordering::Equal));
+
+    let n_pos = sorted.iter().filter(|&&(_, pos)| pos).count();
+    let n_neg = sorted.len() - n_pos;
+
+    let mut points = vec![(0.0, 0.0)];
+    let (mut tp, mut fp) = (0, 0);
+    for (i, &(score, pos)) in sorted.iter().enumerate() {
+        if pos {
+            tp += 1;
+        } else {
+            fp += 1;
+        }
+
+        // No threshold can separate tied scores, so they share a point
+        if i + 1 == sorted.len() || sorted[i + 1].0 != score {
+            points.push((ratio(fp, n_neg), ratio(tp, n_pos)));
+        }
+    }
+
+    points
+}
+
+/// The area under the ROC curve of roc_curve, computed from the ranks of the scores
+/// (the Mann-Whitney U statistic) with ties counting half. This is the probability that a random
+/// positive sample scores higher than a random negative one, and NaN if there are no positive or
+/// no negative samples.
+#[cfg(feature="std")]
+pub fn roc_auc(scored: &[(f64, bool)]) -> f64 {
+    let mut sorted = scored.to_vec();
+    sorted.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(Ordering::Equal));
+
+    let n_pos = sorted.iter().filter(|&&(_, pos)| pos).count();
+    let n_neg = sorted.len() - n_pos;
+    if n_pos == 0 || n_neg == 0 {
+        return f64::NAN;
+    }
+
+    // Each run of tied scores shares the average of the 1-based ranks it covers
+    let mut pos_rank_sum = 0.0;
+    let mut start = 0;
+    while start < sorted.len() {
+        let mut end = start + 1;
+        while end < sorted.len() && sorted[end].0 == sorted[start].0 {
+            end += 1;
+        }
+
+        let rank = (start + 1 + end) as f64 / 2.0;
+        let n_tied_pos = sorted[start..end].iter().filter(|&&(_, pos)| pos).count();
+        pos_rank_sum += rank * n_tied_pos as f64;
+
+        start = end;
+    }
+
+    let (n_pos, n_neg) = (n_pos as f64, n_neg as f64);
+    (pos_rank_sum - n_pos * (n_pos + 1.0) / 2.0) / (n_pos * n_neg)
+}
@@ -1,9 +1,13 @@
 extern crate libc;
 
-use self::libc::{c_int, c_double, c_char};
+use self::libc::{c_int, c_uint, c_double, c_char};
 use ::SvmNode;
+use ::param::{KernelType, SvmType};
 use std::default::Default;
+use std::sync::atomic::{AtomicBool, Ordering};
 
+// Stock libsvm's svm_problem. The instance-weight variant of libsvm appends a W field, which
+// this deliberately doesn't have, see SvmProblem.
 #[repr(C)]
 pub struct CSvmProblem {
     pub l: i32,
@@ -11,30 +15,12 @@ pub struct CSvmProblem {
     pub x: *mut *mut SvmNode
 }
 
-#[repr(C)]
-#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
-pub enum SvmType {
-    CSvc,
-    NuSvc,
-    OneClass,
-    EpsilonSvr,
-    NuSvr,
-}
-
-#[repr(C)]
-#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
-pub enum KernelType {
-    Linear,
-    Poly,
-    Rbf,
-    Sigmoid,
-    Precomputed,
-}
-
+/// libsvm's svm_parameter, as embedded in CSvmModel. The types are plain ints, as in C, since
+/// a model from libsvm (or from_raw) could hold a value SvmType and KernelType don't define.
 #[repr(C)]
 pub struct CSvmParameter {
-    pub svm_type: SvmType,
-    pub kernel_type: KernelType,
+    pub svm_type: c_int,
+    pub kernel_type: c_int,
     pub degree: i32,
     pub gamma: f64,
     pub coef0: f64,
@@ -55,8 +41,8 @@ impl Default for CSvmParameter {
   fn default() -> CSvmParameter {
     use std::ptr;
     CSvmParameter {
-      svm_type: SvmType::CSvc,
-      kernel_type: KernelType::Linear,
+      svm_type: SvmType::CSvc as c_int,
+      kernel_type: KernelType::Linear as c_int,
       degree: 0,
       gamma: 0.0,
       coef0: 0.0,
@@ -75,6 +61,7 @@ impl Default for CSvmParameter {
   }
 }
 
+/// libsvm's svm_model, as handed out by SvmModel::into_raw.
 #[repr(C)]
 pub struct CSvmModel {
     pub param: CSvmParameter,
@@ -130,6 +117,45 @@ extern "C" {
     pub fn svm_check_probability_model(model: *const CSvmModel) -> c_int;
 
     pub fn svm_set_print_string_function(func: extern fn(*const c_char));
+
+    #[link_name = "libsvm_version"]
+    static LIBSVM_VERSION: c_int;
+}
+
+/// The oldest and newest LIBSVM_VERSION whose svm_parameter and svm_model match CSvmParameter
+/// and CSvmModel: sv_indices was added to svm_model in 3.17, and 3.30 inserted prob_density_marks
+/// between probB and sv_indices, which moves every field after it.
+pub const SUPPORTED_LIBSVM_VERSIONS: (i32, i32) = (317, 325);
+
+static VERSION_CHECKED: AtomicBool = AtomicBool::new(false);
+
+/// The version of the linked libsvm, as its libsvm_version global (e.g. 324 for 3.24).
+pub fn libsvm_version() -> i32 {
+    unsafe { LIBSVM_VERSION }
+}
+
+/// Checks that the linked libsvm lays out its structs the way this crate expects, see
+/// SUPPORTED_LIBSVM_VERSIONS. Anything that hands libsvm a struct or reads one back calls this first,
+/// since with a mismatched version it would silently read and write the wrong fields.
+pub fn check_libsvm_version() -> Result<(), String> {
+    if VERSION_CHECKED.load(Ordering::Relaxed) {
+        return Ok(());
+    }
+
+    let version = libsvm_version();
+    let (oldest, newest) = SUPPORTED_LIBSVM_VERSIONS;
+    if version < oldest || version > newest {
+        return Err(format!("Linked against libsvm version {}, but only versions {} through {} are supported",
+                           version, oldest, newest));
+    }
+
+    VERSION_CHECKED.store(true, Ordering::Relaxed);
+    Ok(())
+}
+
+// libsvm shuffles with the C library's rand(), so seeding it has to go through srand()
+extern "C" {
+    pub fn srand(seed: c_uint);
 }
 
 pub extern "C" fn no_output(_: *const c_char) {
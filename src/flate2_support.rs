@@ -0,0 +1,66 @@
+use ::prob::{SvmProblem,ParseError,ParseErrorKind};
+
+use std::fs::File;
+use std::io::{self,BufReader,Read,Seek,SeekFrom};
+
+use ::flate2::read::GzDecoder;
+
+// The first two bytes of every gzip stream
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+impl SvmProblem {
+    /// Reads a gzip-compressed SVMLight file (e.g. a .svm.gz benchmark dataset), decompressing
+    /// it as it's parsed with from_svmlight_reader.
+    pub fn from_svmlight_gz(path: &str) -> Result<SvmProblem, ParseError> {
+        let file = match File::open(path) {
+            Err(err) => { return Err(open_error(path, err)); },
+            Ok(file) => file,
+        };
+
+        SvmProblem::from_svmlight_reader(BufReader::new(GzDecoder::new(file)))
+    }
+
+    /// Reads an SVMLight file that may or may not be gzip-compressed. The file is decompressed
+    /// if its name ends in .gz or it starts with the gzip magic number, and read as plain
+    /// text otherwise.
+    pub fn from_svmlight_auto(path: &str) -> Result<SvmProblem, ParseError> {
+        let mut file = match File::open(path) {
+            Err(err) => { return Err(open_error(path, err)); },
+            Ok(file) => file,
+        };
+
+        let gzipped = path.ends_with(".gz") || match has_gzip_magic(&mut file) {
+            Err(err) => { return Err(open_error(path, err)); },
+            Ok(magic) => magic,
+        };
+
+        if gzipped {
+            SvmProblem::from_svmlight_reader(BufReader::new(GzDecoder::new(file)))
+        } else {
+            SvmProblem::from_svmlight_reader(BufReader::new(file))
+        }
+    }
+}
+
+// Peeks at the start of the file, leaving it rewound
+fn has_gzip_magic(file: &mut File) -> io::Result<bool> {
+    let mut magic = [0; 2];
+    let mut read = 0;
+    while read < magic.len() {
+        match file.read(&mut magic[read..])? {
+            0 => { break; },
+            n => { read += n; },
+        }
+    }
+
+    file.seek(SeekFrom::Start(0))?;
+    Ok(read == magic.len() && magic == GZIP_MAGIC)
+}
+
+fn open_error(path: &str, err: io::Error) -> ParseError {
+    ParseError {
+        line_number: 0,
+        column: 0,
+        kind: ParseErrorKind::Io(format!("Could not open {}: {}", path, err)),
+    }
+}
@@ -0,0 +1,59 @@
+use core::fmt;
+
+/// What a label means. libsvm stores every label as an f64, but for classification (and one-class)
+/// models they're really integer class labels, while for regression they're continuous targets.
+/// Using this instead of a bare f64 keeps a regression output from being mistaken for a class.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Label {
+    /// A class label, for classification and one-class models.
+    Class(i32),
+    /// A continuous target, for regression models.
+    Target(f64),
+}
+
+impl Label {
+    /// The label as libsvm stores it.
+    pub fn as_f64(&self) -> f64 {
+        match *self {
+            Label::Class(class) => class as f64,
+            Label::Target(target) => target,
+        }
+    }
+
+    /// The class label, or None if this is a regression target.
+    pub fn class(&self) -> Option<i32> {
+        match *self {
+            Label::Class(class) => Some(class),
+            Label::Target(_) => None,
+        }
+    }
+
+    /// The regression target, or None if this is a class label.
+    pub fn target(&self) -> Option<f64> {
+        match *self {
+            Label::Class(_) => None,
+            Label::Target(target) => Some(target),
+        }
+    }
+}
+
+impl From<i32> for Label {
+    fn from(class: i32) -> Label {
+        Label::Class(class)
+    }
+}
+
+impl From<f64> for Label {
+    fn from(target: f64) -> Label {
+        Label::Target(target)
+    }
+}
+
+impl fmt::Display for Label {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Label::Class(class) => write!(f, "{}", class),
+            Label::Target(target) => write!(f, "{}", target),
+        }
+    }
+}
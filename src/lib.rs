@@ -1,36 +1,1115 @@
 #![feature(convert)]
+#![cfg_attr(not(feature="std"), no_std)]
 #![cfg_attr(feature="use_clippy", feature(plugin))]
 #![cfg_attr(feature="use_clippy", plugin(clippy))]
+#[cfg(feature="std")]
+extern crate core;
+extern crate alloc;
+#[cfg(feature="std")]
 extern crate rustc_serialize;
+#[cfg(feature="std")]
 extern crate tempfile;
+#[cfg(feature="ndarray")]
+extern crate ndarray;
+#[cfg(feature="nalgebra")]
+extern crate nalgebra;
+#[cfg(feature="rayon")]
+extern crate rayon;
+#[cfg(feature="flate2")]
+extern crate flate2;
+
+use core::fmt;
+#[cfg(feature="std")]
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+#[cfg(feature="std")]
+use std::ffi::CStr;
+#[cfg(feature="std")]
+use std::io;
+#[cfg(feature="std")]
+use std::os::raw::c_char;
 
 mod datavec;
+#[cfg(feature="std")]
 mod prob;
+#[cfg(feature="std")]
 mod ffi; 
+#[cfg(feature="std")]
 mod model;
 mod param;
+mod rng;
+mod precomputed;
+mod eval;
+#[cfg(feature="std")]
+mod cache;
+#[cfg(feature="std")]
+mod dump;
+#[cfg(feature="std")]
+mod timing;
+#[cfg(feature="std")]
+mod ensemble;
+mod label;
+#[cfg(feature="std")]
+mod predictor;
+#[cfg(feature="std")]
+mod binary;
+#[cfg(all(feature="std", feature="ndarray"))]
+mod ndarray_support;
+#[cfg(all(feature="std", feature="nalgebra"))]
+mod nalgebra_support;
+#[cfg(all(feature="std", feature="flate2"))]
+mod flate2_support;
 
-pub use self::datavec::{DataVec};
-pub use self::prob::{SvmProblem};
-pub use self::ffi::{KernelType,SvmType,svm_set_print_string_function};
-pub use self::model::{SvmModel};
-pub use self::param::{SvmParameter,KernelParam,SvmTypeParam};
+pub use self::datavec::{DataVec,ParseDataVecError,DataVecError};
+#[cfg(feature="std")]
+pub use self::prob::{SvmProblem,SvmProblemBuilder,TrainError,CheckParameterError,ProblemError,ParseError,ParseErrorKind};
+#[cfg(feature="std")]
+pub use self::ffi::{CSvmModel,CSvmParameter,svm_set_print_string_function};
+#[cfg(feature="std")]
+pub use self::ffi::{libsvm_version,check_libsvm_version,SUPPORTED_LIBSVM_VERSIONS};
+#[cfg(feature="std")]
+pub use self::model::{SvmModel,PredictError,ModelSummary,LabeledDecision};
+pub use self::param::{SvmParameter,KernelParam,SvmTypeParam,ParamError,ArgsError,KernelType,SvmType};
+pub use self::precomputed::{PrecomputedKernel};
+pub use self::eval::{ConfusionMatrix};
+#[cfg(feature="std")]
+pub use self::cache::{PredictionCache};
+#[cfg(feature="std")]
+pub use self::dump::{ModelDump};
+#[cfg(feature="std")]
+pub use self::ensemble::{SvmEnsemble};
+pub use self::label::{Label};
+#[cfg(feature="std")]
+pub use self::predictor::{Predictor};
+#[cfg(feature="timing")]
+pub use self::timing::{last_train_duration,last_predict_duration,last_cross_validation_duration};
 
-#[derive(Copy, Clone, Debug)]
+/// A single (index, value) component of a sparse vector, laid out the same as
+/// libsvm's svm_node. An index of -1 terminates a vector.
+#[derive(Copy, Clone)]
 #[repr(C)]
 pub struct SvmNode(pub i32, pub f64);
 
+impl SvmNode {
+    /// Makes a node with the feature index and its value.
+    pub fn new(index: i32, value: f64) -> SvmNode {
+        SvmNode(index, value)
+    }
+
+    /// The feature index.
+    pub fn index(&self) -> i32 {
+        self.0
+    }
+
+    /// The value of the feature.
+    pub fn value(&self) -> f64 {
+        self.1
+    }
+}
+
+/// Prints the node the same way it appears in a libsvm data file, `index:value`.
+impl fmt::Debug for SvmNode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.0, self.1)
+    }
+}
+
+#[cfg(feature="std")]
+static OUTPUT_SILENCED: AtomicBool = AtomicBool::new(false);
+
 /// This causes `libsvm` to not produce any output to stdout. This is a wrapper over
 /// `svm_set_print_string_function` with an internal `extern "C"` blank print function.
+///
+/// Calling this more than once does nothing after the first time.
+#[cfg(feature="std")]
 pub fn squelch_output() {
+	if OUTPUT_SILENCED.swap(true, Ordering::SeqCst) {
+		return;
+	}
+
 	unsafe {
 		svm_set_print_string_function(ffi::no_output);
 	}
 }
 
+/// How much of libsvm's output to let through, see set_verbosity.
+#[cfg(feature="std")]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Verbosity {
+	/// Nothing, the same as squelch_output.
+	Silent,
+	/// Only the lines libsvm marks as warnings (e.g. "WARNING: reaching max number of iterations"),
+	/// without the optimization progress.
+	Warnings,
+	/// Everything, the same as libsvm's default.
+	Full,
+}
+
+#[cfg(feature="std")]
+static VERBOSITY: AtomicUsize = AtomicUsize::new(2);
+
+/// Chooses how much libsvm prints to stdout. This installs a print function with
+/// `svm_set_print_string_function`, so it replaces squelch_output (or any print function
+/// set directly) and vice versa.
+///
+/// libsvm doesn't say what kind of message it's printing, so Warnings works by passing on
+/// only the messages that contain "WARNING".
+#[cfg(feature="std")]
+pub fn set_verbosity(level: Verbosity) {
+	VERBOSITY.store(level as usize, Ordering::SeqCst);
+	OUTPUT_SILENCED.store(level == Verbosity::Silent, Ordering::SeqCst);
+
+	unsafe {
+		match level {
+			Verbosity::Silent => svm_set_print_string_function(ffi::no_output),
+			_ => svm_set_print_string_function(filtered_output),
+		}
+	}
+}
+
+#[cfg(feature="std")]
+extern "C" fn filtered_output(msg: *const c_char) {
+	use std::io::Write;
+
+	let msg = unsafe { CStr::from_ptr(msg) }.to_string_lossy();
+	let show = match VERBOSITY.load(Ordering::SeqCst) {
+		l if l == Verbosity::Full as usize => true,
+		l if l == Verbosity::Warnings as usize => msg.contains("WARNING"),
+		_ => false,
+	};
+
+	if show {
+		let stdout = io::stdout();
+		let mut stdout = stdout.lock();
+		let _ = stdout.write_all(msg.as_bytes());
+		let _ = stdout.flush();
+	}
+}
+
+// Prints one of this library's own warnings to stdout alongside libsvm's, so that squelch_output
+// and set_verbosity (which lets through anything marked WARNING) apply to it as well.
+#[cfg(feature="std")]
+fn warn(msg: &str) {
+	use std::io::Write;
+
+	if OUTPUT_SILENCED.load(Ordering::SeqCst) {
+		return;
+	}
+
+	let stdout = io::stdout();
+	let mut stdout = stdout.lock();
+	let _ = writeln!(stdout, "WARNING: {}", msg);
+	let _ = stdout.flush();
+}
+
+/// Whether squelch_output (or set_verbosity with Silent) was the last to set libsvm's output.
+/// This can't see calls to `svm_set_print_string_function` made directly.
+#[cfg(feature="std")]
+pub fn is_output_silenced() -> bool {
+	OUTPUT_SILENCED.load(Ordering::SeqCst)
+}
+
+/// Seeds the C library's random number generator, which libsvm uses for shuffling in
+/// cross validation and when computing probability estimates. Seeding before each call makes
+/// those results reproducible between runs.
+///
+/// Note that the sequence rand() produces for a seed is up to the C library, so the results
+/// will only be the same on the same platform. The generator is also global, so anything else
+/// in the process using rand() will be affected. SvmProblem::cross_validation_with_folds doesn't
+/// have either problem.
+#[cfg(feature="std")]
+pub fn seed_rng(seed: u32) {
+	unsafe {
+		ffi::srand(seed);
+	}
+}
+
+#[cfg(feature="std")]
 mod test {
     #[test]
     fn make_it_link() {
 
     }
+
+    #[test]
+    fn empty_datavec_is_terminated() {
+        use ::DataVec;
+
+        for v in [DataVec::from_sparse(vec![]), DataVec::from_dense(vec![0.0, 0.0])] {
+            assert_eq!(v.len(), 1);
+            assert_eq!(v[0].0, -1);
+        }
+    }
+
+    #[test]
+    fn read_svmlight() {
+        use ::{SvmProblem,ParseErrorKind};
+
+        let data = "+1 1:0.5 3:2 # comment\n\n-1 2:1.5\n";
+        let prob = SvmProblem::from_svmlight_reader(data.as_bytes()).unwrap();
+        assert_eq!(prob.labels(), &[1.0, -1.0]);
+        assert_eq!(prob.vectors()[0].to_string(), "1:0.5 3:2");
+
+        assert!(SvmProblem::from_svmlight_reader_limited(data.as_bytes(), 2).is_err());
+        match SvmProblem::from_svmlight_reader("1 1:0.5\n\n-1  1:1 2:1.2.3".as_bytes()) {
+            Err(err) => {
+                assert_eq!((err.line_number, err.column), (3, 11));
+                assert_eq!(err.kind, ParseErrorKind::BadValue("1.2.3".to_string()));
+                assert_eq!(err.to_string(), "line 3: bad value \"1.2.3\"");
+            },
+            Ok(_) => panic!("Parsed a bad value"),
+        }
+    }
+
+    #[test]
+    fn svmlight_indices_must_ascend() {
+        use ::{SvmProblem,ParseErrorKind};
+
+        for &(data, index, previous, column) in &[("1 2:1 1:1", 1, 2, 7), ("1 1:1 3:1 3:2", 3, 3, 11)] {
+            let err = match SvmProblem::from_svmlight_reader(data.as_bytes()) {
+                Err(err) => err,
+                Ok(_) => panic!("Parsed unordered indices in {:?}", data),
+            };
+            assert_eq!(err.kind, ParseErrorKind::IndexOutOfOrder{index: index, previous: previous});
+            assert_eq!((err.line_number, err.column), (1, column));
+        }
+    }
+
+    #[test]
+    fn params_from_crep_are_detached() {
+        use ::ffi::CSvmParameter;
+        use ::param::protected::{param_from_crep,crep};
+        use ::SvmTypeParam;
+
+        let mut labels = vec![1, -1];
+        let mut weights = vec![2.0, 0.5];
+        let c_param = CSvmParameter {
+            nr_weight: 2,
+            weight_label: labels.as_mut_ptr(),
+            weight: weights.as_mut_ptr(),
+            c: 1.0,
+            ..CSvmParameter::default()
+        };
+
+        let mut param = param_from_crep(&c_param).unwrap();
+        weights[0] = 100.0;
+        drop(labels);
+        drop(weights);
+
+        match param.svm_type_param {
+            SvmTypeParam::CSvc{ref mut weights, ..} => {
+                assert_eq!(weights.len(), 2);
+                assert_eq!((weights[0].label, weights[0].weight), (1, 2.0));
+                weights[1].weight = 3.0;
+            },
+            _ => panic!("Wrong SVM type"),
+        }
+
+        let new_crep = crep(&param);
+        unsafe {
+            assert_eq!(*new_crep.weight.offset(1), 3.0);
+        }
+    }
+
+    #[test]
+    fn params_from_crep_reject_unknown_types() {
+        use ::ffi::CSvmParameter;
+        use ::param::protected::param_from_crep;
+        use ::{SvmTypeParam,KernelParam};
+
+        let rbf = CSvmParameter {
+            kernel_type: 2,
+            gamma: 0.5,
+            ..CSvmParameter::default()
+        };
+        let param = param_from_crep(&rbf).unwrap();
+        match (param.kernel_param, param.svm_type_param) {
+            (KernelParam::Rbf{gamma}, SvmTypeParam::CSvc{..}) => assert_eq!(gamma, 0.5),
+            _ => panic!("Wrong kernel or SVM type"),
+        }
+
+        assert!(param_from_crep(&CSvmParameter{svm_type: 5, ..CSvmParameter::default()}).is_err());
+        assert!(param_from_crep(&CSvmParameter{kernel_type: -1, ..CSvmParameter::default()}).is_err());
+    }
+
+    #[test]
+    fn type_conversions_reject_unknown_values() {
+        use std::convert::TryFrom;
+        use ::{SvmType,KernelType};
+
+        assert_eq!(SvmType::try_from(3), Ok(SvmType::EpsilonSvr));
+        assert_eq!(KernelType::try_from(2), Ok(KernelType::Rbf));
+        assert!(SvmType::try_from(5).is_err());
+        assert!(KernelType::try_from(-1).is_err());
+    }
+
+    #[test]
+    fn linked_libsvm_is_supported() {
+        use ::{libsvm_version,check_libsvm_version,SUPPORTED_LIBSVM_VERSIONS};
+
+        let (oldest, newest) = SUPPORTED_LIBSVM_VERSIONS;
+        assert!(oldest <= newest);
+        assert!(libsvm_version() > 0);
+        assert_eq!(check_libsvm_version().is_ok(), oldest <= libsvm_version() && libsvm_version() <= newest);
+    }
+
+    #[test]
+    fn train_rejects_bad_parameters() {
+        use ::{DataVec,SvmProblem,SvmParameter,KernelParam,SvmTypeParam,TrainError};
+
+        let x = vec![DataVec::from_dense(vec![1.0]), DataVec::from_dense(vec![-1.0])];
+        let prob = SvmProblem::new(x, vec![1.0, -1.0]).unwrap();
+        let param = SvmParameter::new(KernelParam::Linear, SvmTypeParam::NuSvc{nu: 1.5},
+                                      true, false, 100.0, 0.001);
+
+        match prob.train(param) {
+            Err(TrainError::InvalidParameter(_)) => {},
+            Err(err) => panic!("Wrong error: {}", err),
+            Ok(_) => panic!("Trained with nu > 1"),
+        }
+    }
+
+    #[test]
+    fn train_within_timeout() {
+        use ::{DataVec,SvmProblem,SvmParameter};
+        use std::time::Duration;
+
+        let x = vec![DataVec::from_dense(vec![1.0]), DataVec::from_dense(vec![-1.0])];
+        let prob = SvmProblem::new(x, vec![1.0, -1.0]).unwrap();
+
+        let model = prob.train_with_timeout(SvmParameter::default_classification(Some(1.0)), Duration::from_secs(60)).unwrap();
+        assert_eq!(model.predict(&DataVec::from_dense(vec![2.0])), 1.0);
+    }
+
+    #[test]
+    fn check_features_against_the_model() {
+        use ::{DataVec,SvmProblem,SvmParameter,KernelParam,PredictError};
+
+        let x = vec![DataVec::from_dense(vec![1.0, 1.0]), DataVec::from_dense(vec![-1.0, -1.0])];
+        let mut param = SvmParameter::default_classification(None);
+        param.kernel_param = KernelParam::Linear;
+        let model = SvmProblem::new(x, vec![1.0, -1.0]).unwrap().train(param).unwrap();
+
+        assert_eq!(model.num_features(), 2);
+        assert_eq!(model.check_features(&DataVec::from_dense(vec![0.5, 2.0])), Ok(()));
+        assert_eq!(model.check_features(&DataVec::from_dense(vec![0.5, 0.0, 2.0])),
+                   Err(PredictError::UnknownFeature{index: 3, num_features: 2}));
+    }
+
+    #[test]
+    fn trained_model_keeps_its_problem_alive() {
+        use ::{DataVec,SvmProblem,SvmParameter};
+
+        let x: Vec<DataVec> = (0..20).map(|i| DataVec::from_dense(vec![i as f64, (i % 4) as f64])).collect();
+        let y = (0..20).map(|i| if i < 10 { 1.0 } else { -1.0 }).collect();
+        let tests = x.clone();
+
+        // The problem is moved into train, and nothing else refers to its vectors afterwards
+        let model = SvmProblem::new(x, y).unwrap()
+            .train(SvmParameter::default_classification(None)).unwrap();
+
+        // Overwrite freed memory if the problem were dropped too early
+        let _churn: Vec<DataVec> = (0..100).map(|_| DataVec::from_dense(vec![-1e9; 3])).collect();
+
+        let svs = model.get_support_vectors();
+        assert!(!svs.is_empty());
+        for sv in &svs {
+            assert!(sv.iter().all(|node| node.value() != -1e9));
+        }
+        for v in &tests {
+            let y = model.predict(v);
+            assert!(y == 1.0 || y == -1.0);
+        }
+    }
+
+    #[test]
+    fn precomputed_kernel_layout() {
+        use ::PrecomputedKernel;
+
+        assert!(PrecomputedKernel::from_gram_matrix(&[vec![1.0, 0.0], vec![0.0]]).is_err());
+
+        let prob = PrecomputedKernel::from_gram_matrix(&[vec![1.0, 0.0], vec![0.0, 1.0]]).unwrap()
+            .into_problem(vec![1.0, -1.0]).unwrap();
+        let row = &prob.vectors()[1];
+        assert_eq!(format!("{:?}", &row[..]), "[0:2, 1:0, 2:1, -1:0]");
+    }
+
+    #[test]
+    fn precomputed_problem_trains() {
+        use ::{PrecomputedKernel,SvmParameter,KernelParam,KernelType,ProblemError,DataVecError};
+
+        // The linear kernel of the points 1, 2, -1 and -2
+        let points = [1.0, 2.0, -1.0, -2.0];
+        let gram: Vec<Vec<f64>> = points.iter().map(|&a| points.iter().map(|&b| a * b).collect()).collect();
+        let prob = PrecomputedKernel::from_gram_matrix(&gram).unwrap()
+            .into_problem(vec![1.0, 1.0, -1.0, -1.0]).unwrap();
+
+        assert_eq!(prob.validate(KernelType::Precomputed), Ok(()));
+        assert_eq!(prob.validate(KernelType::Linear), Err(ProblemError::BadVector(0, DataVecError::BadIndex{position: 0, index: 0})));
+
+        let mut param = SvmParameter::default_classification(None);
+        param.kernel_param = KernelParam::Precomputed;
+        let model = prob.train(param).unwrap();
+
+        assert!(model.get_support_vectors().iter().all(|sv| sv[0].index() == 0));
+        let test = PrecomputedKernel::test_row(&[3.0, 6.0, -3.0, -6.0]);
+        assert_eq!(model.check_features(&test), Ok(()));
+        assert_eq!(model.predict(&test), 1.0);
+    }
+
+    #[test]
+    fn confusion_matrix_scores() {
+        use ::ConfusionMatrix;
+
+        let mut matrix = ConfusionMatrix::new(vec![1, -1]);
+        for &(actual, predicted) in &[(1, 1), (1, 1), (1, -1), (-1, -1), (-1, 1), (2, 1)] {
+            matrix.add(actual, predicted);
+        }
+
+        assert_eq!(matrix.labels(), &[1, -1, 2]);
+        assert_eq!(matrix.count(1, -1), 1);
+        assert_eq!(matrix.precision(1), 0.5);
+        assert_eq!(matrix.recall(-1), 0.5);
+        assert_eq!(matrix.f1(2), 0.0);
+        assert_eq!(matrix.accuracy(), 0.5);
+    }
+
+    #[test]
+    fn command_line_round_trip() {
+        use ::{SvmParameter,KernelParam,SvmTypeParam};
+        use ::param::Weight;
+
+        let args = |s: &str| s.split_whitespace().map(String::from).collect::<Vec<_>>();
+
+        let param = SvmParameter::new(KernelParam::Poly{degree: 2, gamma: 0.125, coef0: 1.5},
+                                      SvmTypeParam::CSvc{c: 8.0, weights: vec![Weight{label: -1, weight: 2.5}]},
+                                      false, true, 200.0, 0.01);
+        let emitted = param.to_command_line_args();
+        assert_eq!(emitted, args("-s 0 -t 1 -d 2 -g 0.125 -r 1.5 -c 8 -w-1 2.5 -m 200 -e 0.01 -h 0 -b 1"));
+        assert_eq!(SvmParameter::from_command_line_args(&emitted).unwrap(), param);
+
+        assert_eq!(SvmParameter::from_command_line_args(&[]).unwrap(), SvmParameter::default_classification(None));
+        assert!(SvmParameter::from_command_line_args(&args("-c")).is_err());
+        assert!(SvmParameter::from_command_line_args(&args("-x 1")).is_err());
+    }
+
+    #[test]
+    fn from_args_parses_svm_train_lines() {
+        use ::{SvmParameter,SvmTypeParam,ArgsError};
+        use ::param::Weight;
+
+        let args = |s: &str| s.split_whitespace().map(String::from).collect::<Vec<_>>();
+
+        assert_eq!(SvmParameter::from_command_line_args(&args("-c")), Err(ArgsError::MissingValue("-c".to_string())));
+        assert_eq!(SvmParameter::from_args(&["-v", "5"]), Err(ArgsError::UnknownFlag("-v".to_string())));
+
+        let param = SvmParameter::from_args(&["svm-train", "-q", "-s", "0", "-g", "0.125", "-w1", "2", "-w2", "3",
+                                              "train.txt", "model.txt"]).unwrap();
+        let mut expected = SvmParameter::default_classification(Some(0.125));
+        expected.svm_type_param = SvmTypeParam::CSvc{c: 1.0, weights: vec![Weight{label: 1, weight: 2.0}, Weight{label: 2, weight: 3.0}]};
+        assert_eq!(param, expected);
+        assert!(SvmParameter::from_args(&["svm-train", "-t", "9"]).is_err());
+    }
+
+    #[test]
+    fn validate_params_without_a_problem() {
+        use ::{SvmParameter,KernelParam,SvmTypeParam,ParamError};
+
+        assert_eq!(SvmParameter::default_classification(None).validate(), Ok(()));
+
+        let mut param = SvmParameter::default_regression(Some(-1.0));
+        assert_eq!(param.validate(), Err(ParamError::NegativeGamma(-1.0)));
+
+        param.kernel_param = KernelParam::Linear;
+        param.svm_type_param = SvmTypeParam::NuSvr{c: 1.0, nu: 0.0};
+        assert_eq!(param.validate(), Err(ParamError::NuOutOfRange(0.0)));
+
+        param.svm_type_param = SvmTypeParam::CSvc{c: -2.0, weights: Vec::new()};
+        assert_eq!(param.validate(), Err(ParamError::NonPositiveC(-2.0)));
+    }
+
+    #[test]
+    fn poly_kernels_need_a_degree() {
+        use ::{SvmParameter,KernelParam,ParamError};
+
+        let mut param = SvmParameter::default_classification(None);
+        param.kernel_param = KernelParam::Poly{degree: 0, gamma: 0.5, coef0: 0.0};
+        assert_eq!(param.validate(), Err(ParamError::ZeroDegree));
+
+        assert_eq!(KernelParam::poly(0, 0.5, 0.0).unwrap_err(), ParamError::ZeroDegree);
+        assert_eq!(KernelParam::poly(3, 0.0, 1.0).unwrap_err(), ParamError::NonPositiveGamma(0.0));
+        assert!(KernelParam::poly(3, 0.5, 1.0).is_ok());
+    }
+
+    #[test]
+    fn check_parameter_errors_from_libsvm_messages() {
+        use ::CheckParameterError;
+
+        assert_eq!(CheckParameterError::from("C <= 0"), CheckParameterError::NonPositiveC);
+        assert_eq!(CheckParameterError::from("C <= 0").to_string(), "C <= 0");
+        assert_eq!(CheckParameterError::from("new message").to_string(), "new message");
+    }
+
+    #[test]
+    fn roc_with_ties() {
+        use ::eval::{roc_curve,roc_auc};
+
+        let scored = [(0.9, true), (0.5, false), (0.5, true), (0.1, false)];
+        assert_eq!(roc_curve(&scored), vec![(0.0, 0.0), (0.0, 0.5), (0.5, 1.0), (1.0, 1.0)]);
+        assert_eq!(roc_auc(&scored), 0.875);
+        assert!(roc_auc(&[(1.0, true)]).is_nan());
+    }
+
+    #[test]
+    fn merge_datavecs() {
+        use ::{DataVec,SvmNode};
+        use std::panic;
+
+        let a = DataVec::from_sparse(vec![SvmNode(1, 1.0), SvmNode(3, 2.0)]);
+        let b = DataVec::from_sparse(vec![SvmNode(3, 0.5), SvmNode(2, 4.0)]);
+
+        assert_eq!(a.merge(&b).to_string(), "1:1 2:4 3:2.5");
+        assert_eq!(a.merge_with_offset(&b, 3).to_string(), "1:1 3:2 5:4 6:0.5");
+
+        // Shifting index 2 to -1 must not be mistaken for a terminator
+        let single = DataVec::from_sparse(vec![SvmNode(2, 1.0)]);
+        assert!(panic::catch_unwind(|| a.merge_with_offset(&single, -3)).is_err());
+    }
+
+    #[test]
+    fn resort_repairs_the_terminator() {
+        use ::{DataVec,SvmNode,DataVecError};
+
+        let mut v = DataVec::from_dense(vec![1.0, 2.0]);
+        v.pop();
+        assert_eq!(v.validate(), Err(DataVecError::MisplacedTerminator));
+        v.resort();
+        assert_eq!(v.validate(), Ok(()));
+        assert_eq!(v.len(), 3);
+
+        v.swap(0, 1);
+        assert_eq!(v.validate(), Err(DataVecError::Unsorted{position: 1}));
+
+        v.insert(0, SvmNode(-1, 0.0));
+        v.resort();
+        assert_eq!(v.to_string(), "1:1 2:2");
+        assert_eq!(v.validate(), Ok(()));
+    }
+
+    #[test]
+    fn into_inner_keeps_the_terminator() {
+        use ::{DataVec,SvmNode};
+
+        let v = DataVec::from_sparse(vec![SvmNode(2, 1.0), SvmNode(1, 2.0)]);
+        assert_eq!(v.as_nodes().len(), 3);
+        assert_eq!(v.as_nodes()[0].index(), 1);
+
+        let nodes = v.into_inner();
+        assert_eq!(nodes.iter().map(|node| node.index()).collect::<Vec<_>>(), vec![1, 2, -1]);
+    }
+
+    #[test]
+    fn precomputed_rows_survive_resort() {
+        use ::{PrecomputedKernel,DataVecError};
+
+        let mut row = PrecomputedKernel::test_row(&[0.5, 0.25]);
+        row[2].1 = 0.75;
+        row.resort();
+        assert_eq!(row.validate(), Ok(()));
+        assert_eq!(format!("{:?}", &row[..]), "[0:0, 1:0.5, 2:0.75, -1:0]");
+
+        row.swap(0, 1);
+        assert_eq!(row.validate(), Err(DataVecError::BadIndex{position: 1, index: 0}));
+    }
+
+    #[test]
+    fn remap_and_hash_features() {
+        use ::{DataVec,SvmNode};
+        use std::collections::HashMap;
+
+        let mapping: HashMap<i32, i32> = vec![(1, 5), (3, 5), (4, 2)].into_iter().collect();
+        let mut v = DataVec::from_sparse(vec![SvmNode(1, 1.0), SvmNode(2, 7.0), SvmNode(3, 2.0), SvmNode(4, 4.0)]);
+
+        assert!(v.remap_indices(&mapping, false).is_err());
+        assert_eq!(v.to_string(), "1:1 2:7 3:2 4:4");
+        v.remap_indices(&mapping, true).unwrap();
+        assert_eq!(v.to_string(), "2:4 5:3");
+
+        let hashed = v.hash_features(1);
+        assert_eq!(hashed.to_string(), "1:7");
+        assert!(v.hash_features(8).iter().all(|&SvmNode(idx, _)| idx == -1 || (1..=8).contains(&idx)));
+    }
+
+    #[test]
+    fn clamp_values_into_a_window() {
+        use ::{DataVec,SvmNode};
+
+        let w = DataVec::from_sparse(vec![SvmNode(1, -3.0), SvmNode(2, 0.0), SvmNode(3, 9.0)]);
+        assert_eq!(w.clamped(0.0, 5.0).to_string(), "2:0 3:5");
+        assert_eq!(w.clamped(-1.0, 1.0).to_string(), "1:-1 2:0 3:1");
+
+        let mut v = w.clone();
+        v.clamp_values(-1.0, 1.0);
+        assert_eq!(v.to_string(), "1:-1 2:0 3:1");
+    }
+
+    #[test]
+    fn map_values_skips_the_terminator() {
+        use ::{DataVec,SvmNode};
+
+        let mut v = DataVec::from_sparse(vec![SvmNode(2, 4.0), SvmNode(5, 3.0)]);
+        v.map_values(|x| x * 2.0);
+        assert_eq!(v.to_string(), "2:8 5:6");
+
+        v.map_values(|x| x - 6.0);
+        assert_eq!(v.to_string(), "2:2 5:0");
+        assert_eq!(v.last().map(|&SvmNode(idx, val)| (idx, val)), Some((-1, 0.0)));
+    }
+
+    #[test]
+    fn sparse_dot_and_kernels() {
+        use ::{DataVec,SvmNode,KernelParam};
+
+        let a = DataVec::from_dense(vec![1.0, 0.0, 2.0]);
+        let b = DataVec::from_sparse(vec![SvmNode(3, 4.0), SvmNode(2, 5.0)]);
+
+        assert_eq!(a.dot(&b), 8.0);
+        assert_eq!(KernelParam::Linear.evaluate(&a, &b), 8.0);
+        assert_eq!(KernelParam::Poly{degree: 2, gamma: 0.5, coef0: 1.0}.evaluate(&a, &b), 25.0);
+        assert_eq!(KernelParam::Rbf{gamma: 0.5}.evaluate(&a, &a), 1.0);
+    }
+
+    #[test]
+    fn stratified_split_keeps_proportions() {
+        use ::{DataVec,SvmProblem};
+
+        let x = (0..20).map(|i| DataVec::from_dense(vec![i as f64])).collect();
+        let y = (0..20).map(|i| if i < 10 { 1.0 } else { -1.0 }).collect();
+        let (train, test) = SvmProblem::new(x, y).unwrap().stratified_split(0.8, 42);
+
+        assert_eq!(train.labels().len(), 16);
+        assert_eq!(test.labels().iter().filter(|&&l| l == 1.0).count(), 2);
+    }
+
+    #[test]
+    fn class_distribution_counts_labels() {
+        use ::{DataVec,SvmProblem};
+
+        let x = (0..7).map(|i| DataVec::from_dense(vec![i as f64])).collect();
+        let y = vec![2.0, -1.0, 2.0, 2.0, 0.0, -1.0, 2.0];
+        let prob = SvmProblem::new(x, y).unwrap();
+
+        assert_eq!(prob.class_distribution(), vec![(-1.0, 2), (0.0, 1), (2.0, 4)]);
+    }
+
+    #[test]
+    fn nu_is_capped_by_class_balance() {
+        use ::{DataVec,SvmProblem,SvmTypeParam};
+
+        let x = (0..12).map(|i| DataVec::from_dense(vec![i as f64])).collect();
+        let y = (0..12).map(|i| if i < 3 { 1.0 } else { -1.0 }).collect();
+        let prob = SvmProblem::new(x, y).unwrap();
+
+        assert_eq!(prob.max_feasible_nu(), 0.5);
+        assert!(SvmTypeParam::nu_svc_for(0.5, &prob).is_ok());
+        assert!(SvmTypeParam::nu_svc_for(0.6, &prob).is_err());
+    }
+
+    #[test]
+    fn model_dump_json_round_trip() {
+        use ::{DataVec,ModelDump,SvmType,KernelParam};
+        use ::rustc_serialize::json;
+
+        let dump = ModelDump {
+            svm_type: SvmType::CSvc,
+            kernel_param: KernelParam::Rbf{gamma: 0.5},
+            labels: vec![1, -1],
+            n_sv: vec![1, 1],
+            support_vectors: vec![DataVec::from_dense(vec![1.0]), DataVec::from_dense(vec![0.0, 2.0])],
+            sv_coef: vec![vec![0.25, -0.25]],
+            rho: vec![0.1],
+        };
+
+        let decoded: ModelDump = json::decode(&json::encode(&dump).unwrap()).unwrap();
+        assert_eq!(decoded.svm_type, SvmType::CSvc);
+        assert_eq!(decoded.support_vectors, dump.support_vectors);
+        assert_eq!(decoded.sv_coef, dump.sv_coef);
+        match decoded.kernel_param {
+            KernelParam::Rbf{gamma} => assert_eq!(gamma, 0.5),
+            other => panic!("Wrong kernel {:?}", other),
+        }
+    }
+
+    #[test]
+    fn model_dump_predicts_like_libsvm() {
+        use ::{DataVec,SvmProblem,SvmParameter};
+
+        let x: Vec<DataVec> = (0..30).map(|i| {
+            let t = i as f64 / 10.0;
+            DataVec::from_dense(vec![t.sin(), t.cos(), t * 0.3])
+        }).collect();
+        let y = (0..30).map(|i| (i % 3) as f64).collect();
+        let tests = x.clone();
+
+        let model = SvmProblem::new(x, y).unwrap()
+            .train(SvmParameter::default_classification(Some(0.5))).unwrap();
+        let dump = model.to_model_dump();
+
+        for v in &tests {
+            let (y, dec_values) = model.predict_values(v, None).unwrap();
+            let (dump_y, dump_dec_values) = dump.predict_values(v);
+
+            assert_eq!(y, dump_y);
+            for (a, b) in dec_values.iter().zip(&dump_dec_values) {
+                assert!((a - b).abs() < 1e-9, "{} != {}", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn short_dec_values_are_grown() {
+        use ::{DataVec,SvmProblem,SvmParameter};
+
+        let x: Vec<DataVec> = (0..12).map(|i| DataVec::from_dense(vec![(i % 3) as f64, 1.0])).collect();
+        let y = (0..12).map(|i| (i % 3) as f64).collect();
+        let model = SvmProblem::new(x, y).unwrap()
+            .train(SvmParameter::default_classification(Some(0.5))).unwrap();
+
+        let (_, dec_values) = model.predict_values(&DataVec::from_dense(vec![1.0, 1.0]), Some(vec![0.0])).unwrap();
+        assert_eq!(dec_values.len(), 3);
+    }
+
+    #[test]
+    fn predict_values_rejects_non_finite_features() {
+        use ::{DataVec,SvmProblem,SvmParameter,Predictor,PredictionCache,PredictError};
+
+        let x: Vec<DataVec> = (0..12).map(|i| DataVec::from_dense(vec![(i % 3) as f64, 1.0])).collect();
+        let y = (0..12).map(|i| (i % 3) as f64).collect();
+        let model = SvmProblem::new(x, y).unwrap()
+            .train(SvmParameter::default_classification(Some(0.5))).unwrap();
+        let bad = DataVec::from_dense(vec![1.0, ::std::f64::NAN]);
+
+        match model.predict_values(&bad, None) {
+            Err(PredictError::NonFiniteFeature(_)) => {},
+            other => panic!("Expected a NonFiniteFeature error, got {:?}", other),
+        }
+        assert!(model.predict_values_labeled(&bad).is_err());
+
+        let mut predictor = Predictor::new(&model);
+        assert!(predictor.predict_values(&bad).is_err());
+        assert!(predictor.predict_values(&DataVec::from_dense(vec![1.0, 1.0])).is_ok());
+
+        let mut cache = PredictionCache::new(&model);
+        assert!(cache.predict_values(&bad).is_err());
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn thresholds_move_the_boundary() {
+        use ::{DataVec,SvmProblem,SvmParameter};
+
+        let x: Vec<DataVec> = (0..20).map(|i| DataVec::from_dense(vec![i as f64 / 10.0])).collect();
+        let y = (0..20).map(|i| if i < 10 { 1.0 } else { -1.0 }).collect();
+        let model = SvmProblem::new(x, y).unwrap()
+            .train(SvmParameter::default_classification(Some(1.0))).unwrap();
+
+        let v = DataVec::from_dense(vec![0.5]);
+        let positive = model.get_labels(None)[0];
+        assert_eq!(model.predict_with_margin_threshold(&v, 0.0), model.predict(&v) as i32);
+        assert_eq!(model.predict_with_margin_threshold(&v, -1e9), positive);
+        assert!(model.predict_with_margin_threshold(&v, 1e9) != positive);
+    }
+
+    #[test]
+    fn linear_models_have_feature_weights() {
+        use ::{DataVec,SvmProblem,SvmParameter,KernelParam};
+
+        let x: Vec<DataVec> = (0..20).map(|i| DataVec::from_dense(vec![i as f64 / 10.0])).collect();
+        let y = (0..20).map(|i| if i < 10 { 1.0 } else { -1.0 }).collect();
+        let prob = SvmProblem::new(x, y).unwrap();
+
+        let rbf = prob.clone().train(SvmParameter::default_classification(Some(1.0))).unwrap();
+        assert!(rbf.feature_weights().is_none());
+
+        let mut linear = SvmParameter::default_classification(None);
+        linear.kernel_param = KernelParam::Linear;
+        let linear = prob.train(linear).unwrap();
+        let w = linear.feature_weights().unwrap();
+        let x = DataVec::from_dense(vec![0.7]);
+        assert!((w[0][0] * 0.7 - linear.get_rho()[0] - linear.decision_margin(&x)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn margin_violations_on_the_training_set() {
+        use ::{DataVec,SvmProblem,SvmParameter};
+
+        let x: Vec<DataVec> = (0..20).map(|i| DataVec::from_dense(vec![i as f64 / 10.0])).collect();
+        let y = (0..20).map(|i| if i < 10 { 1.0 } else { -1.0 }).collect();
+        let prob = SvmProblem::new(x, y).unwrap();
+        let model = prob.clone().train(SvmParameter::default_classification(Some(1.0))).unwrap();
+
+        assert!(model.margin_violations(&prob) < prob.len());
+
+        let unknown = SvmProblem::new(vec![DataVec::from_dense(vec![0.5])], vec![7.0]).unwrap();
+        assert_eq!(model.margin_violations(&unknown), 0);
+    }
+
+    #[test]
+    fn predictor_reuses_its_buffers() {
+        use ::{DataVec,SvmProblem,SvmParameter,Predictor};
+
+        let x: Vec<DataVec> = (0..12).map(|i| DataVec::from_dense(vec![(i % 3) as f64, 1.0])).collect();
+        let y = (0..12).map(|i| (i % 3) as f64).collect();
+        let model = SvmProblem::new(x.clone(), y).unwrap()
+            .train(SvmParameter::default_classification(Some(0.5))).unwrap();
+
+        let mut predictor = Predictor::new(&model);
+        for v in &x {
+            let expected = model.predict_values(v, None).unwrap();
+            let (y, dec_values) = predictor.predict_values(v).unwrap();
+            assert_eq!((y, dec_values), (expected.0, &expected.1[..]));
+        }
+        assert!(predictor.predict_probability(&x[0]).is_err());
+    }
+
+    #[test]
+    fn probabilities_for_a_batch() {
+        use ::{DataVec,SvmProblem,SvmParameter};
+
+        let x: Vec<DataVec> = (0..30).map(|i| DataVec::from_dense(vec![(i % 3) as f64, 1.0])).collect();
+        let y: Vec<f64> = (0..30).map(|i| (i % 3) as f64).collect();
+        let prob = SvmProblem::new(x.clone(), y).unwrap();
+        let plain = prob.clone().train(SvmParameter::default_classification(Some(0.5))).unwrap();
+        assert!(plain.predict_probabilities_batch(&x).is_err());
+
+        let param = SvmParameter::default_classification(Some(0.5)).with_probability(true).unwrap();
+        let model = prob.train(param).unwrap();
+        let (labels, rows) = model.predict_probabilities_batch(&x[..3]).unwrap();
+        assert_eq!(labels, model.get_labels(None));
+        assert_eq!(rows.len(), 3);
+        for (v, row) in x.iter().zip(&rows) {
+            assert_eq!(row, &model.predict_probability(v, None).unwrap().1);
+        }
+    }
+
+    #[test]
+    // The weight caches are interior mutable, but they're left out of Hash and Eq
+    #[allow(clippy::mutable_key_type)]
+    fn params_as_hash_keys() {
+        use ::param::protected::crep;
+        use ::{SvmParameter,SvmTypeParam};
+        use std::collections::HashMap;
+
+        let param = SvmParameter::default_classification(Some(0.5));
+        // Filling the weight cache shouldn't change equality
+        crep(&param);
+
+        let mut scores = HashMap::new();
+        scores.insert(param, 0.9);
+
+        assert_eq!(scores.get(&SvmParameter::default_classification(Some(0.5))), Some(&0.9));
+        assert_eq!(scores.get(&SvmParameter::default_classification(Some(0.25))), None);
+        assert!(SvmTypeParam::NuSvc{nu: 0.5} != SvmTypeParam::OneClass{nu: 0.5});
+    }
+
+    #[test]
+    fn shuffle_keeps_samples_together() {
+        use ::{DataVec,SvmProblem};
+
+        let x = (0..50).map(|i| DataVec::from_dense(vec![i as f64 + 1.0])).collect();
+        let y = (0..50).map(|i| i as f64 + 1.0).collect();
+        let mut prob = SvmProblem::new(x, y).unwrap();
+
+        prob.shuffle(7);
+        assert!(prob.labels().iter().zip(1..).any(|(&l, i)| l != i as f64));
+        for (label, v) in prob.iter() {
+            assert_eq!(v[0].value(), label);
+        }
+    }
+
+    #[test]
+    fn ids_follow_their_samples() {
+        use ::{DataVec,SvmProblem};
+
+        let prob = SvmProblem::from_pairs((0..50).map(|i| (i as f64 + 1.0, DataVec::from_dense(vec![i as f64 + 1.0]))));
+        assert!(prob.ids().is_none());
+        assert!(prob.clone().with_ids(vec![1, 2]).is_err());
+
+        let mut prob = prob.with_ids((1..51).collect()).unwrap();
+        prob.shuffle(7);
+        for (&label, &id) in prob.labels().iter().zip(prob.ids().unwrap()) {
+            assert_eq!(id as f64, label);
+        }
+
+        let sub = prob.subset(&[4, 0]);
+        let (train, test) = prob.split(0.8, 3);
+        let folds = train.k_folds(4, 3);
+        for part in &[&test, &folds[0].1, &sub] {
+            for (&label, &id) in part.labels().iter().zip(part.ids().unwrap()) {
+                assert_eq!(id as f64, label);
+            }
+        }
+    }
+
+    #[test]
+    fn density_of_vectors_and_problems() {
+        use ::{DataVec,SvmNode,SvmProblem};
+
+        let v = DataVec::from_sparse(vec![SvmNode(2, 4.0), SvmNode(5, 3.0)]);
+        assert_eq!(v.density(), 0.4);
+        assert_eq!(DataVec::from_sparse(vec![]).density(), 0.0);
+
+        let x = vec![v, DataVec::from_dense(vec![1.0, 2.0])];
+        let prob = SvmProblem::new(x, vec![1.0, -1.0]).unwrap();
+        assert!((prob.average_density() - 0.7).abs() < 1e-12);
+        assert_eq!(SvmProblem::new(vec![], vec![]).unwrap().average_density(), 0.0);
+    }
+
+    #[test]
+    fn from_pairs_matches_new() {
+        use ::{DataVec,SvmProblem};
+
+        let pairs = SvmProblem::from_pairs((0..4).map(|i| (-(i as f64), DataVec::from_dense(vec![i as f64, 1.0]))));
+        let x = (0..4).map(|i| DataVec::from_dense(vec![i as f64, 1.0])).collect();
+        let y = (0..4).map(|i| -(i as f64)).collect();
+
+        let prob = SvmProblem::new(x, y).unwrap();
+        assert_eq!(pairs.labels(), prob.labels());
+        assert!(pairs.vectors().iter().zip(prob.vectors()).all(|(a, b)| a.to_string() == b.to_string()));
+        assert_eq!(SvmProblem::from_pairs(vec![]).len(), 0);
+    }
+
+    #[test]
+    fn subset_repeats_and_reorders() {
+        use ::{DataVec,SvmProblem};
+
+        let x = (0..5).map(|i| DataVec::from_dense(vec![i as f64 + 1.0])).collect();
+        let y = (0..5).map(|i| i as f64 + 1.0).collect();
+        let prob = SvmProblem::new(x, y).unwrap();
+
+        let sub = prob.subset(&[3, 3, 0]);
+        assert_eq!(sub.labels(), &[4.0, 4.0, 1.0]);
+        assert_eq!(sub.vectors()[1][0].value(), 4.0);
+        assert_eq!(prob.subset(&[]).len(), 0);
+    }
+
+    #[test]
+    fn iterate_a_problem_by_reference() {
+        use ::{DataVec,SvmProblem};
+
+        let x = (0..3).map(|i| DataVec::from_dense(vec![i as f64 + 1.0])).collect();
+        let prob = SvmProblem::new(x, vec![1.0, 2.0, 3.0]).unwrap();
+
+        let mut seen = Vec::new();
+        for (label, v) in &prob {
+            assert_eq!(v[0].value(), label);
+            seen.push(label);
+        }
+        assert_eq!(seen, prob.labels());
+        assert_eq!((&prob).into_iter().count(), prob.iter().count());
+    }
+
+    #[test]
+    fn concat_problems() {
+        use ::{DataVec,SvmProblem,ProblemError};
+
+        let a = SvmProblem::from_pairs(vec![(1.0, DataVec::from_dense(vec![1.0])), (2.0, DataVec::from_dense(vec![2.0]))]);
+        let b = SvmProblem::from_pairs(vec![(1.0, DataVec::from_dense(vec![0.0, 3.0]))]);
+        let empty = SvmProblem::from_pairs(vec![]);
+
+        let both = SvmProblem::concat(&[a.clone(), empty, b]).unwrap();
+        assert_eq!(both.labels(), &[1.0, 2.0, 1.0]);
+        assert_eq!(both.max_feature_index(), 2);
+        drop(a);
+        assert_eq!(both.vectors()[0][0].value(), 1.0);
+
+        let targets = SvmProblem::from_pairs(vec![(0.5, DataVec::from_dense(vec![1.0]))]);
+        assert_eq!(SvmProblem::concat(&[both.clone(), targets.clone()]).err(), Some(ProblemError::MixedLabels(1)));
+        assert_eq!(SvmProblem::concat(&[SvmProblem::from_pairs(vec![]), targets, both]).err(), Some(ProblemError::MixedLabels(2)));
+    }
+
+    #[test]
+    fn validate_problems() {
+        use ::{DataVec,SvmNode,SvmProblem,ProblemError,DataVecError,KernelType};
+
+        let prob = SvmProblem::from_pairs(vec![(1.0, DataVec::from_dense(vec![1.0])), (2.0, DataVec::from_dense(vec![0.0, 3.0]))]);
+        assert_eq!(prob.validate(KernelType::Linear), Ok(()));
+        assert_eq!(prob.validate(KernelType::Precomputed), Err(ProblemError::MissingSerialNumber(0)));
+
+        let nan = SvmProblem::from_pairs(vec![(1.0, DataVec::from_dense(vec![1.0])), (f64::NAN, DataVec::from_dense(vec![2.0]))]);
+        assert_eq!(nan.validate(KernelType::Linear), Err(ProblemError::NonFiniteLabel(1)));
+
+        let rows = SvmProblem::from_pairs(vec![(1.0, DataVec::from_sparse(vec![SvmNode(0, 1.0), SvmNode(1, 4.0)]))]);
+        assert_eq!(rows.validate(KernelType::Precomputed), Ok(()));
+        assert_eq!(rows.validate(KernelType::Rbf), Err(ProblemError::BadVector(0, DataVecError::BadIndex{position: 0, index: 0})));
+    }
+
+    #[test]
+    fn non_finite_features_are_rejected() {
+        use ::{DataVec,SvmNode,SvmProblem,ProblemError,DataVecError,KernelType};
+
+        let inf = DataVec::from_dense(vec![1.0, 0.0, f64::INFINITY]);
+        assert_eq!(inf.validate_finite(), Err(DataVecError::NonFinite{position: 1}));
+        let nan = DataVec::from_sparse(vec![SvmNode(3, f64::NAN)]);
+        assert_eq!(nan.validate_finite(), Err(DataVecError::NonFinite{position: 0}));
+        assert_eq!(DataVec::from_dense(vec![1.0, -2.0]).validate_finite(), Ok(()));
+
+        let bad = SvmProblem::from_pairs(vec![(1.0, DataVec::from_dense(vec![1.0])), (2.0, inf)]);
+        assert_eq!(bad.validate(KernelType::Linear), Err(ProblemError::BadVector(1, DataVecError::NonFinite{position: 1})));
+    }
+
+    #[test]
+    fn typed_labels() {
+        use ::{DataVec,Label,SvmProblemBuilder,ConfusionMatrix};
+
+        let mut builder = SvmProblemBuilder::new();
+        builder.push_label(DataVec::from_dense(vec![1.0]), 2).unwrap();
+        builder.push_label(DataVec::from_dense(vec![2.0]), 0.5).unwrap();
+        assert_eq!(builder.build().labels(), &[2.0, 0.5]);
+
+        assert_eq!(Label::from(3).class(), Some(3));
+        assert_eq!(Label::from(3.0).class(), None);
+
+        let mut matrix = ConfusionMatrix::new(vec![1, -1]);
+        matrix.add_labels(Label::Class(1), Label::Class(-1)).unwrap();
+        assert!(matrix.add_labels(Label::Class(1), Label::Target(1.0)).is_err());
+        assert_eq!(matrix.total(), 1);
+    }
+
+    #[test]
+    fn binary_round_trip() {
+        use ::{DataVec,SvmProblem,PrecomputedKernel};
+
+        let prob = SvmProblem::from_pairs(vec![(1.0, DataVec::from_dense(vec![0.5, 0.0, -2.0])),
+                                               (-1.0, DataVec::from_sparse(vec![]))]);
+        let prob = prob.with_ids(vec![7, 9]).unwrap();
+
+        let mut buf = Vec::new();
+        prob.write_binary(&mut buf).unwrap();
+        let read = SvmProblem::read_binary(&buf[..]).unwrap();
+
+        assert_eq!(read.labels(), prob.labels());
+        assert_eq!(read.vectors(), prob.vectors());
+        assert_eq!(read.ids(), Some(&[7, 9][..]));
+
+        assert!(SvmProblem::read_binary(&buf[..buf.len() - 1]).is_err());
+        assert!(SvmProblem::read_binary(&b"not a problem"[..]).is_err());
+
+        let precomputed = PrecomputedKernel::from_gram_matrix(&[vec![1.0, 0.5], vec![0.5, 1.0]]).unwrap()
+            .into_problem(vec![1.0, -1.0]).unwrap();
+        let mut buf = Vec::new();
+        precomputed.write_binary(&mut buf).unwrap();
+        assert_eq!(SvmProblem::read_binary(&buf[..]).unwrap().vectors(), precomputed.vectors());
+    }
+
+    #[test]
+    fn parse_and_display_datavec() {
+        use ::{DataVec,ParseDataVecError};
+
+        let v: DataVec = "4:9.2 1:0.3".parse().unwrap();
+        assert_eq!(v.to_string(), "1:0.3 4:9.2");
+
+        assert_eq!("1:0.3 4".parse::<DataVec>().unwrap_err(), ParseDataVecError::MissingColon("4".to_string()));
+        assert_eq!("0:1".parse::<DataVec>().unwrap_err(), ParseDataVecError::BadIndex("0:1".to_string()));
+        assert_eq!("2:x".parse::<DataVec>().unwrap_err(), ParseDataVecError::BadValue("2:x".to_string()));
+    }
 }
\ No newline at end of file
@@ -1,31 +1,111 @@
+extern crate libc;
+
 use ::ffi;
-use ::ffi::{CSvmModel,SvmType};
-use ::param::SvmParameter;
+use ::ffi::{CSvmModel};
+use ::param::{SvmParameter,SvmTypeParam,KernelParam,SvmType};
 use ::prob::SvmProblem;
-use ::datavec::DataVec;
+use ::datavec::{DataVec,DataVecError};
+use ::eval::ConfusionMatrix;
+use ::dump::ModelDump;
+use ::label::Label;
+use ::timing::{self,Timed};
 use std::ffi::{CString};
-use std::mem;
-use std::ops::Drop;
+use std::convert::TryFrom;
+use std::ops::{Drop,Range};
 use std::io::{Read,Write};
 use std::fs;
 use std::fs::File;
+use std::cell::Cell;
+use std::slice;
+use std::cmp::Ordering;
+use std::fmt;
+use std::error::Error;
+use std::mem;
+use std::collections::HashMap;
+use self::libc::{size_t};
+use ::SvmNode;
 
 use ::rustc_serialize::{Encodable,Decodable,Encoder,Decoder};
 use ::tempfile::NamedTempFile;
 
+/// The reasons a prediction can fail.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PredictError {
+    /// A probability prediction was requested from a model that wasn't trained with
+    /// probability estimates.
+    NoProbabilityModel,
+    /// The input vector has a NaN or infinite value, see DataVec::validate_finite.
+    NonFiniteFeature(DataVecError),
+    /// The input vector has a feature index above the model's num_features, see check_features.
+    UnknownFeature{index: i32, num_features: i32},
+}
+
+impl fmt::Display for PredictError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PredictError::NoProbabilityModel => write!(f, "The model was not trained with probability estimates"),
+            PredictError::NonFiniteFeature(ref err) => write!(f, "Bad input vector: {}", err),
+            PredictError::UnknownFeature{index, num_features} => {
+                write!(f, "The input vector has feature index {}, but the model only uses up to {}", index, num_features)
+            },
+        }
+    }
+}
+
+impl Error for PredictError {}
+
+/// A decision value paired with the (first, second) labels it decides between, see
+/// SvmModel::predict_values_labeled.
+pub type LabeledDecision = ((i32, i32), f64);
+
+/// A bundle of the most commonly needed facts about a model, see SvmModel::summary.
+#[derive(Clone, Debug)]
+pub struct ModelSummary {
+    pub svm_type: SvmType,
+    pub nr_class: i32,
+    pub nr_sv: i32,
+    /// Empty for regression and one-class models, which have no labels.
+    pub labels: Vec<i32>,
+    /// Whether check_probability_model is true.
+    pub probability: bool,
+}
+
 /// An SVM Model is a trained Support Vector Machine, which can be used
 /// to query new problems. It manages all lifetimes and memory needed by itself in
 /// concert with libsvm itself (though it may be a little conservative).
-// The dead code is actually for lifetimes
+///
+/// Of the prediction functions, try_predict, predict_values and predict_probability (and the
+/// functions built on them, like predict_values_labeled and predict_probabilities_batch) check the
+/// input for NaN and infinite values. The rest hand it straight to libsvm, which returns garbage
+/// for such a vector rather than failing, and each says which checked function to use instead.
+// The problem and parameters are never read, but the C model may reference
+// their memory so they must live as long as it does.
+//
+// Which one applies is recorded in the C model's free_sv. svm_train sets it to 0: the
+// support vectors point straight into the training problem's DataVecs and libsvm won't free them,
+// so prob must be Some for as long as the model exists (see model_from_c_rep). svm_load_model sets
+// it to 1: libsvm allocated its own copy of the support vectors and frees them itself, so no problem
+// is needed. from_raw can't know, which is why it's unsafe.
 #[allow(dead_code)]
-pub struct SvmModel<'a> {
-	crep: &'a mut CSvmModel,
+pub struct SvmModel {
+    crep: *mut CSvmModel,
 
     param: Option<SvmParameter>,
     prob: Option<SvmProblem>,
+
+    num_features: Cell<Option<i32>>,
 }
 
-impl<'a> SvmModel<'a> {
+impl SvmModel {
+    fn new(crep: *mut CSvmModel, param: Option<SvmParameter>, prob: Option<SvmProblem>) -> SvmModel {
+        SvmModel {
+            crep: crep,
+            param: param,
+            prob: prob,
+            num_features: Cell::new(None),
+        }
+    }
+
     /// Attempts to save the model to a file and reports whether or
     /// not it was successful. Unfortunately, libsvm doesn't report an
     /// error message so neither can we.
@@ -39,24 +119,126 @@ impl<'a> SvmModel<'a> {
     }
 
     /// Loads a model from a file.
+    ///
+    /// Panics if the linked libsvm isn't one of ffi::SUPPORTED_LIBSVM_VERSIONS, since the model it
+    /// returned couldn't be read safely.
     pub fn load(model_file_name: &str) -> Self {
+        if let Err(err) = ffi::check_libsvm_version() {
+            panic!("{}", err);
+        }
+
         unsafe {
             let fname = CString::new(model_file_name).unwrap();
 
-            SvmModel {
-                crep: &mut (*ffi::svm_load_model(fname.as_ptr())),
-                param: None,
-                prob: None,
+            let crep = ffi::svm_load_model(fname.as_ptr());
+            debug_assert!(crep.is_null() || (*crep).free_sv == 1, "Loaded model doesn't own its support vectors");
+
+            SvmModel::new(crep, None, None)
+        }
+    }
+
+    /// Gives up ownership of the underlying libsvm model, e.g. to hand it to other native code.
+    /// The wrapper is forgotten rather than dropped, so svm_free_and_destroy_model is never called and
+    /// whoever holds the pointer becomes responsible for freeing it (or passing it back to from_raw).
+    ///
+    /// A model trained from Rust may point into its problem's memory, so the problem and parameters
+    /// are deliberately leaked along with it to keep that memory valid. They aren't recovered by from_raw.
+    pub fn into_raw(self) -> *mut CSvmModel {
+        let crep = self.crep;
+        mem::forget(self);
+
+        crep
+    }
+
+    /// Takes ownership of a libsvm model, which will be freed with svm_free_and_destroy_model
+    /// when the returned SvmModel is dropped. This is the inverse of into_raw, and can also wrap
+    /// models created by native code.
+    ///
+    /// # Safety
+    ///
+    /// ptr must be a valid, non-null model allocated by libsvm that nothing else will free or use
+    /// afterwards, and any memory it references must outlive the SvmModel.
+    pub unsafe fn from_raw(ptr: *mut CSvmModel) -> SvmModel {
+        SvmModel::new(ptr, None, None)
+    }
+
+    /// Makes an independent copy of this model by saving it to a temporary file and
+    /// loading it back. The copy owns its own libsvm allocation, so either model
+    /// can be dropped without affecting the other.
+    pub fn try_clone(&self) -> Result<SvmModel, String> {
+        let file = match NamedTempFile::new() {
+            Err(err) => { return Err(format!("Could not create temp file: {}", err)); },
+            Ok(file) => file,
+        };
+
+        let path = match file.path().to_str() {
+            None => { return Err("Could not get file name of temp file".to_string()); },
+            Some(path) => path,
+        };
+
+        if !self.save(path) {
+            return Err("Could not save model to temp file".to_string());
+        }
+
+        unsafe {
+            let fname = CString::new(path).unwrap();
+            let crep = ffi::svm_load_model(fname.as_ptr());
+
+            if crep.is_null() {
+                return Err("Could not load model back from temp file".to_string());
             }
+
+            Ok(SvmModel::new(crep, self.param.clone(), None))
         }
     }
 
     /// Returns the type of the SVM, this is one of the values
     /// of the enum SvmType. Please see the libsvm documentation
     /// for more info.
-    pub fn get_svm_type(&self) -> SvmType {
-        unsafe {
-            mem::transmute(ffi::svm_get_svm_type(self.crep))
+    ///
+    /// Returns an error if libsvm reports a type this library doesn't know about, which
+    /// can only happen for a model wrapped with from_raw.
+    pub fn get_svm_type(&self) -> Result<SvmType, String> {
+        let svm_type = unsafe { ffi::svm_get_svm_type(self.crep) };
+
+        SvmType::try_from(svm_type)
+    }
+
+    // For the functions that can't report an unknown SVM type, see get_svm_type.
+    fn known_svm_type(&self) -> SvmType {
+        match self.get_svm_type() {
+            Ok(svm_type) => svm_type,
+            Err(err) => { panic!("{}", err); },
+        }
+    }
+
+    /// Whether this is a classification model (CSvc or NuSvc). A model of an unknown
+    /// type (see get_svm_type) is none of classification, regression or one-class.
+    pub fn is_classification(&self) -> bool {
+        matches!(self.get_svm_type(), Ok(SvmType::CSvc) | Ok(SvmType::NuSvc))
+    }
+
+    /// Whether this is a regression model (EpsilonSvr or NuSvr).
+    pub fn is_regression(&self) -> bool {
+        matches!(self.get_svm_type(), Ok(SvmType::EpsilonSvr) | Ok(SvmType::NuSvr))
+    }
+
+    /// Whether this is a one-class (distribution estimation) model.
+    pub fn is_one_class(&self) -> bool {
+        self.get_svm_type() == Ok(SvmType::OneClass)
+    }
+
+    /// Collects the SVM type, number of classes and support vectors, labels, and whether
+    /// it's a probability model in one struct, e.g. for logging a model after loading it.
+    ///
+    /// Panics if the SVM type is unknown (see get_svm_type).
+    pub fn summary(&self) -> ModelSummary {
+        ModelSummary {
+            svm_type: self.known_svm_type(),
+            nr_class: self.get_nr_class(),
+            nr_sv: self.get_nr_sv(),
+            labels: if self.is_classification() { self.get_labels(None) } else { Vec::new() },
+            probability: self.check_probability_model(),
         }
     }
 
@@ -70,13 +252,11 @@ impl<'a> SvmModel<'a> {
 
     /// Get a list of labels that can be used to label an input.
     /// If buf is supplied it will be used as the target and returned
-    /// back to you, however, make sure that it is at least get_nr_class in length,
-    /// and capacity or bugs could happen.
+    /// back to you. If it's shorter than get_nr_class it will be grown to fit first.
     pub fn get_labels(&self, buf: Option<Vec<i32>>) -> Vec<i32> {
+        let size = self.get_nr_class() as usize;
         let mut buf = match buf {
             None => {
-                let size = self.get_nr_class() as usize;
-
                 let mut buf = Vec::with_capacity(size);
                 unsafe {
                     buf.set_len(size);
@@ -84,7 +264,7 @@ impl<'a> SvmModel<'a> {
 
                 buf
             },
-            Some(buf) => buf,
+            Some(buf) => grow_to(buf, size),
         };
 
         unsafe {
@@ -94,6 +274,44 @@ impl<'a> SvmModel<'a> {
         buf
     }
 
+    /// Changes the model's class labels, so that predict (and everything else reporting labels)
+    /// gives mapping[label] wherever it used to give label. e.g. {-1: 0, 1: 1} turns a model trained
+    /// on -1/1 into one predicting 0/1. The labels of any class weights in the parameters are changed too.
+    ///
+    /// The mapping must cover every label of the model and map them to distinct labels, otherwise
+    /// an error is returned and nothing is changed. Only classification models have labels to change.
+    pub fn relabel(&mut self, mapping: &HashMap<i32, i32>) -> Result<(), String> {
+        if !self.is_classification() {
+            return Err("Only classification models have labels".to_string());
+        }
+
+        let mut new_labels = Vec::with_capacity(self.get_nr_class() as usize);
+        for label in self.get_labels(None) {
+            match mapping.get(&label) {
+                None => { return Err(format!("No mapping for label {}", label)); },
+                Some(new_label) if new_labels.contains(new_label) => {
+                    return Err(format!("Label {} is mapped to by more than one label", new_label));
+                },
+                Some(&new_label) => new_labels.push(new_label),
+            }
+        }
+
+        unsafe {
+            let crep = &mut *self.crep;
+            slice::from_raw_parts_mut(crep.label, new_labels.len()).copy_from_slice(&new_labels);
+        }
+
+        if let Some(SvmParameter{svm_type_param: SvmTypeParam::CSvc{ref mut weights, ..}, ..}) = self.param {
+            for weight in weights.iter_mut() {
+                if let Some(&new_label) = mapping.get(&weight.label) {
+                    weight.label = new_label;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
     /// Returns the number of support vectors.
     pub fn get_nr_sv(&self) -> i32 {
         unsafe {
@@ -101,12 +319,26 @@ impl<'a> SvmModel<'a> {
         }
     }
 
+    /// Returns the number of support vectors for each class, in the same order as get_labels.
+    /// Regression and one-class models don't keep this, so it will be empty for them.
+    pub fn get_n_sv_per_class(&self) -> Vec<i32> {
+        unsafe {
+            let crep = &*self.crep;
+            if crep.n_sv.is_null() {
+                return Vec::new();
+            }
+
+            slice::from_raw_parts(crep.n_sv, crep.nr_class as usize).to_vec()
+        }
+    }
+
     /// Returns the indices of the support vectors.
+    /// If buf is supplied it will be used as the target and returned
+    /// back to you. If it's shorter than get_nr_sv it will be grown to fit first.
     pub fn get_sv_indices(&self, buf: Option<Vec<i32>>) -> Vec<i32> {
+        let size = self.get_nr_sv() as usize;
         let mut buf = match buf {
             None => {
-                let size = self.get_nr_sv() as usize;
-
                 let mut buf = Vec::with_capacity(size);
                 unsafe {
                     buf.set_len(size);
@@ -114,7 +346,7 @@ impl<'a> SvmModel<'a> {
 
                 buf
             },
-            Some(buf) => buf,
+            Some(buf) => grow_to(buf, size),
         };
         unsafe {
             ffi::svm_get_sv_indices(self.crep, buf.as_mut_ptr());
@@ -139,26 +371,38 @@ impl<'a> SvmModel<'a> {
     /// If this is a regression, only dec_values[0] is valid and contains the regression info, and the f64
     /// is the same regression value.
     ///
-    /// Please see the libsvm documentation for more info, and make sure the dec_values passed in is of the right size.
-    /// If none is supplied, it will always allocate as if this is a decision problem.
+    /// Please see the libsvm documentation for more info.
+    /// If none is supplied, it will always allocate as if this is a decision problem (see Predictor
+    /// to avoid allocating on every call). A supplied
+    /// dec_values shorter than decision_function_count is grown to that length before libsvm writes to it.
+    ///
+    /// Returns PredictError::NonFiniteFeature if test_vec has a NaN or infinite value.
     pub fn predict_values(&self,
                           test_vec: &DataVec,
                           dec_values: Option<Vec<f64>>)
-                          -> (f64, Vec<f64>) {
-        let mut dec_values = match dec_values {
-            None => {
-                let nr_class = self.get_nr_class();
-                let len = (nr_class*(nr_class-1)/2) as usize;
+                          -> Result<(f64, Vec<f64>), PredictError> {
+        if let Err(err) = test_vec.validate_finite() {
+            return Err(PredictError::NonFiniteFeature(err));
+        }
 
-                let mut dec_values = Vec::with_capacity(len);
-                unsafe {
-                    dec_values.set_len(len);
+        Ok(self.decision_values(test_vec, dec_values))
+    }
+
+    // predict_values without the check, for the functions that don't return a PredictError.
+    fn decision_values(&self, test_vec: &DataVec, dec_values: Option<Vec<f64>>) -> (f64, Vec<f64>) {
+        let mut dec_values = match dec_values {
+            None => vec![0.0; self.decision_function_count()],
+            Some(mut dec_values) => {
+                let len = self.decision_function_count();
+                if dec_values.len() < len {
+                    dec_values.resize(len, 0.0);
                 }
 
                 dec_values
             },
-            Some(dec_values) => dec_values,
         };
+        debug_assert!(dec_values.len() >= self.decision_function_count());
+
         let y;
         unsafe {
             y = ffi::svm_predict_values(self.crep, test_vec.as_ptr(), dec_values.as_mut_ptr());
@@ -167,33 +411,396 @@ impl<'a> SvmModel<'a> {
         (y, dec_values)
     }
 
+    /// The single decision value of a binary classifier, its (unnormalized) signed distance from
+    /// the separating hyperplane. A positive margin predicts get_labels()[0] and a negative one
+    /// get_labels()[1], so e.g. with labels [1, -1] the sign matches the predicted label, but with
+    /// [-1, 1] it's the opposite. This is the per-sample score to use for threshold tuning or ROC curves.
+    ///
+    /// For regression and one-class models this is their one decision value as well.
+    /// Panics for classifiers with more than two classes, which have one value per pair of classes.
+    ///
+    /// x isn't checked for NaN or infinite values, use predict_values for that.
+    pub fn decision_margin(&self, x: &DataVec) -> f64 {
+        assert!(self.decision_function_count() == 1,
+                "decision_margin needs a binary model, this one has {} classes", self.get_nr_class());
+
+        self.decision_values(x, None).1[0]
+    }
+
+    /// The number of samples of prob (normally the training set) that are on the wrong side of
+    /// their class's margin: misclassified, or correctly classified with a decision_margin of
+    /// magnitude below 1. These are the samples that end up as bounded support vectors, so many of
+    /// them suggests underfitting (or a C that's too small), and almost none overfitting.
+    /// Samples with labels the model doesn't know are skipped.
+    ///
+    /// Panics if this isn't a binary classification model.
+    pub fn margin_violations(&self, prob: &SvmProblem) -> usize {
+        assert!(self.is_classification(), "margin_violations needs a classification model");
+        let labels = self.get_labels(None);
+
+        prob.iter()
+            .filter(|&(label, _)| labels.contains(&(label as i32)))
+            .filter(|&(label, v)| {
+                let sign = if label as i32 == labels[0] { 1.0 } else { -1.0 };
+                sign * self.decision_margin(v) < 1.0
+            })
+            .count()
+    }
+
+    /// The number of decision values predict_values produces, nr_class*(nr_class-1)/2.
+    /// For regression and one-class models this is 1.
+    pub fn decision_function_count(&self) -> usize {
+        let nr_class = self.get_nr_class() as usize;
+        nr_class * (nr_class - 1) / 2
+    }
+
+    /// Returns the pair of labels each of predict_values' decision values corresponds to,
+    /// in the same order. This is libsvm's one-vs-one ordering: (label1, label2), (label1, label3),
+    /// ..., (label2, label3), etc. A positive decision value is a vote for the first label of the pair.
+    ///
+    /// Regression and one-class models have no labels, so this will be empty for them.
+    pub fn decision_pairs(&self) -> Vec<(i32, i32)> {
+        if !self.is_classification() {
+            return Vec::new();
+        }
+
+        let labels = self.get_labels(None);
+        let mut pairs = Vec::with_capacity(self.decision_function_count());
+
+        for i in 0..labels.len() {
+            for j in i+1..labels.len() {
+                pairs.push((labels[i], labels[j]));
+            }
+        }
+
+        pairs
+    }
+
+    /// The same as predict_values, except each decision value is paired with
+    /// the labels it decides between (see decision_pairs). This is only meaningful for
+    /// classification models, for others the list will be empty.
+    pub fn predict_values_labeled(&self, test_vec: &DataVec) -> Result<(f64, Vec<LabeledDecision>), PredictError> {
+        let (y, dec_values) = self.predict_values(test_vec, None)?;
+
+        Ok((y, self.decision_pairs().into_iter().zip(dec_values).collect()))
+    }
+
+    /// Aggregates the one-vs-one decision values into one-vs-rest scores using the same
+    /// Max-Wins voting libsvm uses internally. Each pair's decision value is a vote for
+    /// the first label if positive and the second otherwise, and the score for each label is its
+    /// number of votes. The labels are in the same order as get_labels.
+    ///
+    /// This is only meaningful for classification models, for others the list will be empty.
+    /// test_vec isn't checked for NaN or infinite values, use predict_values_labeled for that.
+    pub fn ovr_scores(&self, test_vec: &DataVec) -> Vec<(i32, f64)> {
+        self.aggregate_decisions(test_vec, |dec| if dec > 0.0 { (1.0, 0.0) } else { (0.0, 1.0) })
+    }
+
+    /// The same as ovr_scores, except instead of counting votes, each label's score is the sum
+    /// of the decision values for every pair it's in (negated when it's the second label of the pair).
+    /// This gives a finer grained confidence than the vote count. As with ovr_scores, test_vec
+    /// isn't checked for NaN or infinite values.
+    pub fn ovr_margin_sums(&self, test_vec: &DataVec) -> Vec<(i32, f64)> {
+        self.aggregate_decisions(test_vec, |dec| (dec, -dec))
+    }
+
+    /// Accumulates per-label scores, where score maps a pair's decision value into the
+    /// amounts to add to the first and second label respectively.
+    fn aggregate_decisions<F: Fn(f64) -> (f64, f64)>(&self, test_vec: &DataVec, score: F) -> Vec<(i32, f64)> {
+        let (_, dec_values) = self.decision_values(test_vec, None);
+        let decisions: Vec<LabeledDecision> = self.decision_pairs().into_iter().zip(dec_values).collect();
+        if decisions.is_empty() {
+            return Vec::new();
+        }
+
+        let mut scores: Vec<(i32, f64)> = self.get_labels(None).into_iter().map(|label| (label, 0.0)).collect();
+
+        for ((label1, label2), dec) in decisions {
+            let (score1, score2) = score(dec);
+
+            for &mut (label, ref mut total) in &mut scores {
+                if label == label1 {
+                    *total += score1;
+                } else if label == label2 {
+                    *total += score2;
+                }
+            }
+        }
+
+        scores
+    }
+
+    /// Returns copies of the model's support vectors, in the same order as get_sv_indices.
+    ///
+    /// For precomputed kernels, each starts with the 0:sample_serial_number node
+    /// PrecomputedKernel puts in front of the kernel values.
+    pub fn get_support_vectors(&self) -> Vec<DataVec> {
+        unsafe {
+            let crep = &*self.crep;
+            let svs = slice::from_raw_parts(crep.sv, crep.l as usize);
+
+            svs.iter().map(|&sv| {
+                let mut len = 0;
+                while (*sv.offset(len)).0 != -1 {
+                    len += 1;
+                }
+
+                // libsvm's vectors are already sorted and, with the terminator included, in the
+                // layout DataVec uses, so there's no need to sort them again.
+                ::datavec::protected::from_raw(slice::from_raw_parts(sv, len as usize + 1).to_vec())
+            }).collect()
+        }
+    }
+
+    /// Returns the support vectors (with their coefficients) that make up the decision function
+    /// between the classes at indices i and j of get_labels. This undoes libsvm's layout, where the
+    /// coefficients for each pair are interleaved across the rows of sv_coef and offset by the number of
+    /// support vectors of the classes before them.
+    ///
+    /// The coefficients are signed so the function is positive for class i, so swapping i and j negates
+    /// them. This is only meaningful for classification models, for others the list will be empty.
+    ///
+    /// Panics if i and j are the same or either is not below get_nr_class.
+    pub fn sv_coef_for_pair(&self, i: usize, j: usize) -> Vec<(DataVec, f64)> {
+        let nr_class = self.get_nr_class() as usize;
+        assert!(i != j && i < nr_class && j < nr_class,
+                "Invalid class pair ({}, {}) for {} classes", i, j, nr_class);
+
+        let n_sv = self.get_n_sv_per_class();
+        if n_sv.is_empty() {
+            return Vec::new();
+        }
+
+        let (lo, hi, sign) = if i < j { (i, j, 1.0) } else { (j, i, -1.0) };
+
+        let mut start = vec![0; nr_class];
+        for k in 1..nr_class {
+            start[k] = start[k - 1] + n_sv[k - 1] as usize;
+        }
+
+        let svs = self.get_support_vectors();
+        unsafe {
+            let rows = slice::from_raw_parts((*self.crep).sv_coef, nr_class - 1);
+            let lo_row = slice::from_raw_parts(rows[hi - 1], svs.len());
+            let hi_row = slice::from_raw_parts(rows[lo], svs.len());
+
+            // The lower class' coefficients are in row hi-1, and the higher class' in row lo
+            let lo_svs = (start[lo]..start[lo] + n_sv[lo] as usize).map(|k| (k, lo_row[k]));
+            let hi_svs = (start[hi]..start[hi] + n_sv[hi] as usize).map(|k| (k, hi_row[k]));
+
+            lo_svs.chain(hi_svs).map(|(k, coef)| (svs[k].clone(), sign * coef)).collect()
+        }
+    }
+
+    /// Returns a copy of libsvm's sv_coef, the coefficients of every support vector in each of the
+    /// nr_class-1 rows (see sv_coef_for_pair for how a row maps to a decision function). Regression and
+    /// one-class models have a single row.
+    pub fn get_sv_coef(&self) -> Vec<Vec<f64>> {
+        unsafe {
+            let crep = &*self.crep;
+            let rows = slice::from_raw_parts(crep.sv_coef, (crep.nr_class - 1) as usize);
+
+            rows.iter().map(|&row| slice::from_raw_parts(row, crep.l as usize).to_vec()).collect()
+        }
+    }
+
+    /// Returns the constant term of every decision function, in the same order as predict_values'
+    /// decision values. Each decision value is sum(coef*K(sv, x)) - rho.
+    pub fn get_rho(&self) -> Vec<f64> {
+        unsafe {
+            slice::from_raw_parts((*self.crep).rho, self.decision_function_count()).to_vec()
+        }
+    }
+
+    /// Collects everything needed to evaluate the model's decision function into one
+    /// plain struct, which can be serialized and moved to other runtimes.
+    ///
+    /// Panics if the SVM or kernel type is unknown, as for view_params.
+    pub fn to_model_dump(&self) -> ModelDump {
+        let param = self.view_params();
+
+        ModelDump {
+            svm_type: self.known_svm_type(),
+            kernel_param: param.kernel_param,
+            labels: if self.is_classification() { self.get_labels(None) } else { Vec::new() },
+            n_sv: self.get_n_sv_per_class(),
+            support_vectors: self.get_support_vectors(),
+            sv_coef: self.get_sv_coef(),
+            rho: self.get_rho(),
+        }
+    }
+
+    /// The highest feature index used by any of the support vectors. Since a feature that
+    /// never appears in a support vector has no effect on the model, any test vector with
+    /// a higher index was probably prepared differently from the training data (see check_features).
+    /// The 0 index of a precomputed kernel's sample serial numbers isn't counted.
+    ///
+    /// This is computed the first time it's called and cached afterwards.
+    pub fn num_features(&self) -> i32 {
+        if let Some(num_features) = self.num_features.get() {
+            return num_features;
+        }
+
+        let num_features = self.get_support_vectors().iter()
+            .flat_map(|v| v.iter().map(|&SvmNode(idx, _)| idx))
+            .filter(|&idx| idx > 0)
+            .max()
+            .unwrap_or(0);
+
+        self.num_features.set(Some(num_features));
+        num_features
+    }
+
+    /// For a linear kernel, the explicit weight vector w of each decision function, which is then
+    /// w.x - rho (see get_rho). Each w is dense, with w[i] the weight of feature index i+1, and has
+    /// num_features entries. For other kernels there's no such vector, so this is None.
+    ///
+    /// There is one w per decision function, so for multiclass models this gives a weight vector for
+    /// every pair of classes, in the order of decision_pairs. Binary, regression and one-class models
+    /// have just the one.
+    pub fn feature_weights(&self) -> Option<Vec<Vec<f64>>> {
+        match self.view_params().kernel_param {
+            KernelParam::Linear => {},
+            _ => { return None; },
+        }
+
+        let svs = self.get_support_vectors();
+        let sv_coef = self.get_sv_coef();
+        let dim = self.num_features() as usize;
+
+        let weigh = |w: &mut Vec<f64>, coef: &[f64], range: Range<usize>| {
+            for k in range {
+                for &SvmNode(idx, val) in svs[k].iter().filter(|node| node.0 > 0) {
+                    w[idx as usize - 1] += coef[k] * val;
+                }
+            }
+        };
+
+        if !self.is_classification() {
+            let mut w = vec![0.0; dim];
+            weigh(&mut w, &sv_coef[0], 0..svs.len());
+            return Some(vec![w]);
+        }
+
+        // The same layout as ModelDump::predict uses: class i's support vectors start at start[i],
+        // and for the pair (i, j) class i's coefficients are in row j-1 while class j's are in row i.
+        let n_sv = self.get_n_sv_per_class();
+        let mut start = vec![0; n_sv.len()];
+        for i in 1..n_sv.len() {
+            start[i] = start[i - 1] + n_sv[i - 1] as usize;
+        }
+
+        let mut weights = Vec::with_capacity(self.decision_function_count());
+        for i in 0..n_sv.len() {
+            for j in i+1..n_sv.len() {
+                let mut w = vec![0.0; dim];
+                weigh(&mut w, &sv_coef[j - 1], start[i]..start[i] + n_sv[i] as usize);
+                weigh(&mut w, &sv_coef[i], start[j]..start[j] + n_sv[j] as usize);
+                weights.push(w);
+            }
+        }
+
+        Some(weights)
+    }
+
     /// Predicts the class or regression value of the test vector test_vec.
     /// This is effectively predict_values without the dec_values component.
+    ///
+    /// test_vec isn't checked for NaN or infinite values, which give a meaningless prediction
+    /// (try_predict returns an error instead). In debug builds, such a value or an index above
+    /// num_features is printed as a warning along with libsvm's output (see check_features and
+    /// set_verbosity), but the prediction still goes ahead.
     pub fn predict(&self, test_vec: &DataVec) -> f64 {
-        unsafe {
+        if cfg!(debug_assertions) {
+            if let Err(err) = test_vec.validate_finite() {
+                ::warn(&PredictError::NonFiniteFeature(err).to_string());
+            }
+            if let Err(err) = self.check_features(test_vec) {
+                ::warn(&err.to_string());
+            }
+        }
+
+        timing::record(Timed::Predict, || unsafe {
             ffi::svm_predict(self.crep, test_vec.as_ptr()) as f64
+        })
+    }
+
+    /// The same as predict, but returns an error instead of a meaningless prediction if
+    /// test_vec has a NaN or infinite value.
+    pub fn try_predict(&self, test_vec: &DataVec) -> Result<f64, PredictError> {
+        match test_vec.validate_finite() {
+            Err(err) => Err(PredictError::NonFiniteFeature(err)),
+            Ok(()) => Ok(self.predict(test_vec)),
         }
     }
 
+    /// Checks that test_vec has no feature index above num_features, which is a common sign
+    /// that the test data was prepared differently from the training data. libsvm itself doesn't
+    /// mind (the extra features just have no effect), so only debug builds of predict call this,
+    /// and only to print a warning.
+    pub fn check_features(&self, test_vec: &DataVec) -> Result<(), PredictError> {
+        let num_features = self.num_features();
+
+        match test_vec.iter().find(|&&SvmNode(idx, _)| idx > num_features) {
+            Some(&SvmNode(idx, _)) => Err(PredictError::UnknownFeature{index: idx, num_features: num_features}),
+            None => Ok(()),
+        }
+    }
+
+    /// The same as predict, but typed by what the model predicts: a Label::Class for classification
+    /// and one-class models, and a Label::Target for regression. Like predict, this doesn't check
+    /// test_vec for NaN or infinite values, see try_predict.
+    pub fn predict_label(&self, test_vec: &DataVec) -> Label {
+        let y = self.predict(test_vec);
+
+        if self.is_regression() {
+            Label::Target(y)
+        } else {
+            Label::Class(y as i32)
+        }
+    }
+
+    /// A convenience for predict on a dense feature vector, converted with DataVec::from_dense_slice.
+    /// Like predict, this doesn't check x for NaN or infinite values, see try_predict.
+    pub fn predict_from_dense(&self, x: &[f64]) -> f64 {
+        self.predict(&DataVec::from_dense_slice(x))
+    }
+
+    /// A convenience for predict_probability on a dense feature vector, converted with
+    /// DataVec::from_dense_slice.
+    pub fn predict_probability_from_dense(&self, x: &[f64]) -> Result<(f64, Vec<f64>), PredictError> {
+        self.predict_probability(&DataVec::from_dense_slice(x), None)
+    }
+
     /// Predicts the class of the feature vector test_vec based on its probability of belonging to a
-    /// certain class. This only works correctly if check_probability_model returns true, please check that
-    /// first and see the libsvm documentation for more info.
+    /// certain class. This only works if check_probability_model returns true (i.e. the model was trained
+    /// with probability on), otherwise PredictError::NoProbabilityModel is returned. Please see the
+    /// libsvm documentation for more info.
+    ///
+    /// A supplied prob_estimates shorter than get_nr_class is grown to that length before libsvm writes to it.
     pub fn predict_probability(&self,
                                test_vec: &DataVec,
                                prob_estimates: Option<Vec<f64>>)
-                               -> (f64, Vec<f64>) {
+                               -> Result<(f64, Vec<f64>), PredictError> {
+        if !self.check_probability_model() {
+            return Err(PredictError::NoProbabilityModel);
+        }
+        if let Err(err) = test_vec.validate_finite() {
+            return Err(PredictError::NonFiniteFeature(err));
+        }
+
         let mut prob_estimates = match prob_estimates {
-            None => {
+            None => vec![0.0; self.get_nr_class() as usize],
+            Some(mut prob_estimates) => {
                 let len = self.get_nr_class() as usize;
-                let mut prob_estimates = Vec::with_capacity(len);
-                unsafe {
-                    prob_estimates.set_len(len);
+                if prob_estimates.len() < len {
+                    prob_estimates.resize(len, 0.0);
                 }
 
                 prob_estimates
             },
-            Some(prob_estimates) => prob_estimates,
         };
+        debug_assert!(prob_estimates.len() >= self.get_nr_class() as usize);
 
         let p;
         unsafe {
@@ -201,7 +808,102 @@ impl<'a> SvmModel<'a> {
                                         prob_estimates.as_mut_ptr()) as f64;
         }
 
-        (p, prob_estimates)
+        Ok((p, prob_estimates))
+    }
+
+    /// predict_probability over a batch of inputs, as a table: the labels heading the columns
+    /// (get_labels), and a row of probabilities for each input in those columns' order. libsvm
+    /// writes every input's probabilities into the same buffer, which is copied into the row.
+    /// Like predict_probability, this returns an error if check_probability_model is false.
+    pub fn predict_probabilities_batch(&self, inputs: &[DataVec]) -> Result<(Vec<i32>, Vec<Vec<f64>>), PredictError> {
+        let mut buf = Some(Vec::new());
+        let mut rows = Vec::with_capacity(inputs.len());
+
+        for x in inputs {
+            let (_, prob_estimates) = self.predict_probability(x, buf.take())?;
+            rows.push(prob_estimates.clone());
+            buf = Some(prob_estimates);
+        }
+
+        Ok((self.get_labels(None), rows))
+    }
+
+    /// The same as predict_probability, except each probability is paired with the label
+    /// it's for, and they're sorted from most to least probable. The predicted label is
+    /// returned alongside. Like predict_probability, this returns an error if
+    /// check_probability_model is false.
+    pub fn predict_probability_map(&self, test_vec: &DataVec) -> Result<(i32, Vec<(i32, f64)>), PredictError> {
+        let (p, prob_estimates) = self.predict_probability(test_vec, None)?;
+
+        let mut probs: Vec<(i32, f64)> = self.get_labels(None).into_iter().zip(prob_estimates).collect();
+        probs.sort_by(|&(_, p1), &(_, p2)| p2.partial_cmp(&p1).unwrap_or(Ordering::Equal));
+
+        Ok((p as i32, probs))
+    }
+
+    /// Predicts every sample of test and tallies the predictions against the true labels.
+    /// The matrix is indexed by get_labels, though any true label the model doesn't know
+    /// about is added as well. This only makes sense for classification models.
+    pub fn confusion_matrix(&self, test: &SvmProblem) -> ConfusionMatrix {
+        let mut matrix = ConfusionMatrix::new(self.get_labels(None));
+
+        for (label, v) in test.iter() {
+            matrix.add(label as i32, self.predict(v) as i32);
+        }
+
+        matrix
+    }
+
+    /// The ROC curve of a binary classifier over test, as (false positive rate, true positive rate)
+    /// points. Samples are scored by decision_margin, so get_labels()[0] is the positive class.
+    ///
+    /// Panics if this isn't a binary classification model.
+    pub fn roc_curve(&self, test: &SvmProblem) -> Vec<(f64, f64)> {
+        ::eval::roc_curve(&self.scored_margins(test))
+    }
+
+    /// The area under roc_curve, which is NaN if test doesn't have samples of both classes.
+    ///
+    /// Panics if this isn't a binary classification model.
+    pub fn roc_auc(&self, test: &SvmProblem) -> f64 {
+        ::eval::roc_auc(&self.scored_margins(test))
+    }
+
+    fn scored_margins(&self, test: &SvmProblem) -> Vec<(f64, bool)> {
+        assert!(self.is_classification(), "ROC curves need a classification model");
+        let positive = self.get_labels(None)[0];
+
+        test.iter().map(|(label, v)| (self.decision_margin(v), label as i32 == positive)).collect()
+    }
+
+    /// Predicts with a binary probability model, but with a tunable decision boundary instead of
+    /// libsvm's: get_labels()[0] is the positive class, and is predicted if its probability is at
+    /// least threshold. The other label is predicted otherwise. Raising the threshold trades recall
+    /// for precision, e.g. on imbalanced problems.
+    ///
+    /// Panics if this isn't a binary classification model with probability estimates, see
+    /// predict_with_margin_threshold for models without them.
+    pub fn predict_with_threshold(&self, x: &DataVec, threshold: f64) -> i32 {
+        assert!(self.is_classification() && self.get_nr_class() == 2,
+                "predict_with_threshold needs a binary classification model, this one has {} classes", self.get_nr_class());
+        let labels = self.get_labels(None);
+
+        match self.predict_probability(x, None) {
+            Ok((_, probs)) => if probs[0] >= threshold { labels[0] } else { labels[1] },
+            Err(err) => panic!("{}", err),
+        }
+    }
+
+    /// The same as predict_with_threshold, but thresholds decision_margin instead of a probability,
+    /// so it works without probability estimates. A threshold of 0.0 agrees with predict.
+    ///
+    /// Panics if this isn't a binary classification model. As with decision_margin, x isn't
+    /// checked for NaN or infinite values.
+    pub fn predict_with_margin_threshold(&self, x: &DataVec, threshold: f64) -> i32 {
+        assert!(self.is_classification(), "predict_with_margin_threshold needs a classification model");
+        let labels = self.get_labels(None);
+
+        if self.decision_margin(x) > threshold { labels[0] } else { labels[1] }
     }
 
     /// Tests whether the model has enough information for probability estimates.
@@ -212,15 +914,140 @@ impl<'a> SvmModel<'a> {
         }
     }
 
+    /// Refits the model's probability estimates on a fresh validation set without retraining.
+    /// For every pair of classes, the decision values of the validation samples belonging to
+    /// either class are fit to a sigmoid with Platt's algorithm (in the same form libsvm uses
+    /// during training), and the resulting prob_a/prob_b are written back into the C model.
+    ///
+    /// This also works on models trained without probability estimates, which will pass
+    /// check_probability_model afterwards. Only classification models are supported, and
+    /// validation samples with labels the model doesn't know about are ignored.
+    pub fn platt_recalibrate(&mut self, validation: &SvmProblem) -> Result<(), String> {
+        if !self.is_classification() {
+            return Err("Only classification models can be recalibrated".to_string());
+        }
+        if validation.is_empty() {
+            return Err("The validation set is empty".to_string());
+        }
+
+        let pairs = self.decision_pairs();
+        let mut decisions: Vec<(Vec<f64>, Vec<bool>)> = vec![(Vec::new(), Vec::new()); pairs.len()];
+
+        for (label, v) in validation.iter() {
+            let label = label as i32;
+            let (_, dec_values) = match self.predict_values(v, None) {
+                Ok(prediction) => prediction,
+                Err(err) => { return Err(format!("Bad validation sample: {}", err)); },
+            };
+
+            for (p, &(label1, label2)) in pairs.iter().enumerate() {
+                if label == label1 || label == label2 {
+                    decisions[p].0.push(dec_values[p]);
+                    decisions[p].1.push(label == label1);
+                }
+            }
+        }
+
+        unsafe {
+            let crep = &mut *self.crep;
+            // libsvm frees these with free() when the model is destroyed
+            if crep.prob_a.is_null() {
+                crep.prob_a = libc::malloc((pairs.len() * mem::size_of::<f64>()) as size_t) as *mut f64;
+            }
+            if crep.prob_b.is_null() {
+                crep.prob_b = libc::malloc((pairs.len() * mem::size_of::<f64>()) as size_t) as *mut f64;
+            }
+            if crep.prob_a.is_null() || crep.prob_b.is_null() {
+                return Err("Could not allocate the probability parameters".to_string());
+            }
+
+            let prob_a = slice::from_raw_parts_mut(crep.prob_a, pairs.len());
+            let prob_b = slice::from_raw_parts_mut(crep.prob_b, pairs.len());
+            for (p, (dec_values, positive)) in decisions.iter().enumerate() {
+                let (a, b) = fit_sigmoid(dec_values, positive);
+                prob_a[p] = a;
+                prob_b[p] = b;
+            }
+
+            crep.param.probability = 1;
+        }
+
+        if let Some(ref mut param) = self.param {
+            param.probability = true;
+        }
+
+        Ok(())
+    }
+
+    /// Replaces the parameters stored in the model without retraining, e.g. to experiment with
+    /// a different kernel gamma at prediction time. Afterwards view_params (and save) reflect
+    /// the new parameters.
+    ///
+    /// The SVM and kernel types can't be changed, since the support vectors and coefficients only
+    /// make sense for the ones the model was trained with, so an error is returned if either differs.
+    /// The model's class weights are kept as well, as they're only used in training.
+    pub fn set_params(&mut self, param: &SvmParameter) -> Result<(), String> {
+        let current = self.view_params();
+        if param.svm_type_param.to_svm_type() != current.svm_type_param.to_svm_type() {
+            return Err(format!("Cannot change the SVM type from {:?} to {:?}",
+                               current.svm_type_param.to_svm_type(), param.svm_type_param.to_svm_type()));
+        }
+        if param.kernel_param.to_kernel_type() != current.kernel_param.to_kernel_type() {
+            return Err(format!("Cannot change the kernel type from {:?} to {:?}",
+                               current.kernel_param.to_kernel_type(), param.kernel_param.to_kernel_type()));
+        }
+
+        unsafe {
+            let new_crep = ::param::protected::crep(param);
+            let c_param = &mut (*self.crep).param;
+
+            // The weight pointers are left alone, they may point into self.param's cache
+            c_param.degree = new_crep.degree;
+            c_param.gamma = new_crep.gamma;
+            c_param.coef0 = new_crep.coef0;
+            c_param.cache_size = new_crep.cache_size;
+            c_param.eps = new_crep.eps;
+            c_param.c = new_crep.c;
+            c_param.nu = new_crep.nu;
+            c_param.p = new_crep.p;
+            c_param.shrinking = new_crep.shrinking;
+            c_param.probability = new_crep.probability;
+        }
+
+        if let Some(ref mut own) = self.param {
+            own.kernel_param = param.kernel_param;
+            own.shrinking = param.shrinking;
+            own.probability = param.probability;
+            own.cache_size = param.cache_size;
+            own.epsilon = param.epsilon;
+
+            // Only the weights the model's C parameters actually point to are kept
+            let mut svm_type_param = param.svm_type_param.clone();
+            if let (SvmTypeParam::CSvc{weights: new_weights, ..}, SvmTypeParam::CSvc{weights, ..})
+                    = (&mut svm_type_param, &own.svm_type_param) {
+                *new_weights = weights.clone();
+            }
+            own.svm_type_param = svm_type_param;
+        }
+
+        Ok(())
+    }
+
     /// View the parameters this model was generated from.
     /// If this was generated using svm_train from the Rust side, it will
     /// be a clone of the struct used to generate the model. If not, (i.e. it was loaded
     /// from file) it will be generated from the internal C struct.
     ///
     /// Either way, this struct is safe to modify or generate future models.
+    ///
+    /// Panics if the C struct has an SVM or kernel type this library doesn't know about, which
+    /// (like an unknown type from get_svm_type) can only happen for a model wrapped with from_raw.
     pub fn view_params(&self) -> SvmParameter {
         match self.param {
-            None => ::param::protected::param_from_crep(&self.crep.param),
+            None => match unsafe { ::param::protected::param_from_crep(&(*self.crep).param) } {
+                Ok(param) => param,
+                Err(err) => { panic!("{}", err); },
+            },
             Some(ref param) => {
                 let mut p = param.clone();
                 ::param::protected::set_in_model(&mut p, false);
@@ -233,7 +1060,7 @@ impl<'a> SvmModel<'a> {
 /// This encodes by saving it to a named temp file and then reading THAT
 /// into a Vec<u8> and encoding it. This is probably a bad idea and you should
 /// probably use a raw `save` if at all possible.
-impl<'a> Encodable for SvmModel<'a> {
+impl Encodable for SvmModel {
     fn encode<S: Encoder>(&self, s: &mut S) -> Result<(), S::Error> {
         // Get a tmp file path by just creating a temp file and getting its handle,
         // then letting it get deleted.
@@ -269,7 +1096,7 @@ impl<'a> Encodable for SvmModel<'a> {
 /// tells libsvm to load a model from that file. This is probably a dumb idea
 /// and you should probably use a raw `load` from a `save`d file if
 /// possible.
-impl<'a> Decodable for SvmModel<'a> {
+impl Decodable for SvmModel {
     fn decode<D: Decoder>(d: &mut D) -> Result<Self, D::Error> {
         let buf = match Vec::<u8>::decode(d) {
             Err(err) => { return Err(err); },
@@ -289,21 +1116,157 @@ impl<'a> Decodable for SvmModel<'a> {
     }
 }
 
-impl<'a> Drop for SvmModel<'a> {
+/// Summarizes the model's structure, leaving out the support vectors themselves.
+impl fmt::Debug for SvmModel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let summary = self.summary();
+
+        f.debug_struct("SvmModel")
+            .field("svm_type", &summary.svm_type)
+            .field("nr_class", &summary.nr_class)
+            .field("labels", &summary.labels)
+            .field("nr_sv", &summary.nr_sv)
+            .field("n_sv_per_class", &self.get_n_sv_per_class())
+            .field("probability", &summary.probability)
+            .finish()
+    }
+}
+
+/// A one line description, e.g. "CSvc model with 2 classes [1, -1] and 12 support vectors".
+impl fmt::Display for SvmModel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let summary = self.summary();
+
+        write!(f, "{:?} model", summary.svm_type)?;
+        if !summary.labels.is_empty() {
+            write!(f, " with {} classes {:?} and", summary.nr_class, summary.labels)?;
+        } else {
+            write!(f, " with")?;
+        }
+        write!(f, " {} support vectors", summary.nr_sv)?;
+        if summary.probability {
+            write!(f, " (probability estimates)")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for SvmModel {
     fn drop(&mut self) {
         unsafe {
-            let mut crep_ref: *mut CSvmModel = self.crep;
+            let mut crep_ref = self.crep;
             ffi::svm_free_and_destroy_model(&mut crep_ref);
         }
     }
 }
 
-pub fn model_from_c_rep(crep: &mut CSvmModel, prob: SvmProblem, mut param: SvmParameter) -> SvmModel {
-    ::param::protected::set_in_model(&mut param, true);
+/// Makes sure a user supplied buffer is at least size long before libsvm writes into it.
+fn grow_to<T: Clone + Default>(mut buf: Vec<T>, size: usize) -> Vec<T> {
+    if buf.len() < size {
+        buf.resize(size, T::default());
+    }
+
+    buf
+}
+
+/// Fits P(positive|f) = 1/(1+exp(a*f+b)) to the decision values f, following libsvm's
+/// sigmoid_train (Lin, Lin and Weng's more stable version of Platt's algorithm).
+fn fit_sigmoid(dec_values: &[f64], positive: &[bool]) -> (f64, f64) {
+    const MAX_ITER: usize = 100;
+    const MIN_STEP: f64 = 1e-10;
+    const SIGMA: f64 = 1e-12;
+    const EPS: f64 = 1e-5;
+
+    let prior1 = positive.iter().filter(|&&pos| pos).count() as f64;
+    let prior0 = positive.len() as f64 - prior1;
+
+    let hi_target = (prior1 + 1.0) / (prior1 + 2.0);
+    let lo_target = 1.0 / (prior0 + 2.0);
+    let targets: Vec<f64> = positive.iter().map(|&pos| if pos { hi_target } else { lo_target }).collect();
+
+    let objective = |a: f64, b: f64| -> f64 {
+        dec_values.iter().zip(&targets).map(|(&dec, &t)| {
+            let f_apb = dec * a + b;
+            if f_apb >= 0.0 {
+                t * f_apb + (-f_apb).exp().ln_1p()
+            } else {
+                (t - 1.0) * f_apb + f_apb.exp().ln_1p()
+            }
+        }).sum()
+    };
+
+    let (mut a, mut b) = (0.0, ((prior0 + 1.0) / (prior1 + 1.0)).ln());
+    let mut fval = objective(a, b);
+
+    for _ in 0..MAX_ITER {
+        // Gradient and Hessian, with the Hessian nudged to stay positive definite
+        let (mut h11, mut h22, mut h21, mut g1, mut g2) = (SIGMA, SIGMA, 0.0, 0.0, 0.0);
+        for (&dec, &t) in dec_values.iter().zip(&targets) {
+            let f_apb = dec * a + b;
+            let (p, q) = if f_apb >= 0.0 {
+                let e = (-f_apb).exp();
+                (e / (1.0 + e), 1.0 / (1.0 + e))
+            } else {
+                let e = f_apb.exp();
+                (1.0 / (1.0 + e), e / (1.0 + e))
+            };
 
-    SvmModel {
-        crep: crep,
-        param: Some(param),
-        prob: Some(prob),
+            let d2 = p * q;
+            h11 += dec * dec * d2;
+            h22 += d2;
+            h21 += dec * d2;
+
+            let d1 = t - p;
+            g1 += dec * d1;
+            g2 += d1;
+        }
+
+        if g1.abs() < EPS && g2.abs() < EPS {
+            break;
+        }
+
+        // Newton direction, then a backtracking line search along it
+        let det = h11 * h22 - h21 * h21;
+        let d_a = -(h22 * g1 - h21 * g2) / det;
+        let d_b = -(-h21 * g1 + h11 * g2) / det;
+        let gd = g1 * d_a + g2 * d_b;
+
+        let mut step = 1.0;
+        while step >= MIN_STEP {
+            let (new_a, new_b) = (a + step * d_a, b + step * d_b);
+            let new_f = objective(new_a, new_b);
+
+            if new_f < fval + 0.0001 * step * gd {
+                a = new_a;
+                b = new_b;
+                fval = new_f;
+                break;
+            }
+
+            step /= 2.0;
+        }
+
+        if step < MIN_STEP {
+            break;
+        }
+    }
+
+    (a, b)
+}
+
+/// Wraps a model fresh out of svm_train, taking ownership of the problem and parameters it was
+/// trained from since its support vectors (and possibly weights) point into them.
+pub fn model_from_c_rep(crep: *mut CSvmModel, prob: SvmProblem, mut param: SvmParameter) -> SvmModel {
+    unsafe {
+        debug_assert!((*crep).free_sv == 0, "Trained model unexpectedly owns its support vectors");
     }
-}
\ No newline at end of file
+    ::param::protected::set_in_model(&mut param, true);
+
+    SvmModel::new(crep, Some(param), Some(prob))
+}
+/// SvmModel::predict_values without the check for NaN and infinite values, for Predictor's
+/// functions that are documented as unchecked.
+pub fn predict_values_unchecked(model: &SvmModel, test_vec: &DataVec, dec_values: Vec<f64>) -> (f64, Vec<f64>) {
+    model.decision_values(test_vec, Some(dec_values))
+}
@@ -0,0 +1,25 @@
+use ::datavec::DataVec;
+use ::prob::SvmProblem;
+
+use ::nalgebra::{DMatrix, DVector};
+
+impl DataVec {
+    /// Builds a DataVec from a dense nalgebra vector. This behaves exactly like
+    /// from_dense, so only elements exactly equal to 0.0 are filtered out.
+    pub fn from_dvector(v: &DVector<f64>) -> DataVec {
+        DataVec::from_dense_slice(v.as_slice())
+    }
+}
+
+impl SvmProblem {
+    /// Builds a problem from a dense matrix and a vector of labels. This assumes
+    /// each ROW of x is one sample (even though nalgebra stores matrices column-major),
+    /// so x must have one row per label or an error is returned, as with new.
+    pub fn from_dmatrix(x: &DMatrix<f64>, y: &DVector<f64>) -> Result<SvmProblem, String> {
+        let x = (0..x.nrows())
+            .map(|i| DataVec::from_dense(x.row(i).iter().cloned().collect()))
+            .collect();
+
+        SvmProblem::new(x, y.as_slice().to_vec())
+    }
+}
@@ -0,0 +1,34 @@
+use ::datavec::DataVec;
+use ::prob::SvmProblem;
+use ::model::SvmModel;
+
+use ::ndarray::{Array1, ArrayView1, ArrayView2};
+
+impl DataVec {
+    /// Builds a DataVec from a dense ndarray row. This behaves exactly like
+    /// from_dense, so only elements exactly equal to 0.0 are filtered out.
+    pub fn from_ndarray(row: ArrayView1<f64>) -> DataVec {
+        match row.as_slice() {
+            Some(slice) => DataVec::from_dense_slice(slice),
+            None => DataVec::from_dense(row.to_vec()),
+        }
+    }
+}
+
+impl SvmProblem {
+    /// Builds a problem from a dense matrix and a vector of labels. Each row of x is
+    /// one sample, and is converted with DataVec::from_ndarray. As with new, this returns
+    /// an error if the number of rows doesn't match the number of labels.
+    pub fn from_ndarray(x: ArrayView2<f64>, y: ArrayView1<f64>) -> Result<SvmProblem, String> {
+        let x = x.rows().into_iter().map(DataVec::from_ndarray).collect();
+
+        SvmProblem::new(x, y.to_vec())
+    }
+}
+
+impl SvmModel {
+    /// Predicts every row of x (one sample per row) and collects the results in order.
+    pub fn predict_batch_ndarray(&self, x: ArrayView2<f64>) -> Array1<f64> {
+        x.rows().into_iter().map(|row| self.predict(&DataVec::from_ndarray(row))).collect()
+    }
+}
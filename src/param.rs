@@ -1,10 +1,81 @@
+#[cfg(feature="std")]
 extern crate libc;
 
-use ::ffi::{CSvmParameter, KernelType, SvmType};
-use std::default::Default;
-use std::cell::RefCell;
+#[cfg(feature="std")]
+use ::ffi::CSvmParameter;
+#[cfg(feature="std")]
+use ::datavec::DataVec;
+#[cfg(feature="std")]
+use ::prob::SvmProblem;
+#[cfg(feature="std")]
+use core::default::Default;
+use core::cell::RefCell;
+use core::hash::{Hash,Hasher};
+use core::fmt;
+use core::error::Error;
+use core::convert::TryFrom;
+use alloc::vec::Vec;
+use alloc::string::{String,ToString};
+use alloc::format;
+#[cfg(feature="std")]
 use self::libc::{c_int};
 
+// libsvm's svm_type and kernel_type, in the same order. They're defined here rather than
+// with the other C types in ffi so the parameters can be built without std.
+#[repr(C)]
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum SvmType {
+	CSvc,
+	NuSvc,
+	OneClass,
+	EpsilonSvr,
+	NuSvr,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum KernelType {
+	Linear,
+	Poly,
+	Rbf,
+	Sigmoid,
+	Precomputed,
+}
+
+impl TryFrom<i32> for SvmType {
+	type Error = String;
+
+	/// Converts libsvm's integer representation of the SVM type, failing on any
+	/// value libsvm doesn't define.
+	fn try_from(val: i32) -> Result<SvmType, String> {
+		match val {
+			0 => Ok(SvmType::CSvc),
+			1 => Ok(SvmType::NuSvc),
+			2 => Ok(SvmType::OneClass),
+			3 => Ok(SvmType::EpsilonSvr),
+			4 => Ok(SvmType::NuSvr),
+			_ => Err(format!("Unknown SVM type: {}", val)),
+		}
+	}
+}
+
+impl TryFrom<i32> for KernelType {
+	type Error = String;
+
+	/// Converts libsvm's integer representation of the kernel type, failing on any
+	/// value libsvm doesn't define.
+	fn try_from(val: i32) -> Result<KernelType, String> {
+		match val {
+			0 => Ok(KernelType::Linear),
+			1 => Ok(KernelType::Poly),
+			2 => Ok(KernelType::Rbf),
+			3 => Ok(KernelType::Sigmoid),
+			4 => Ok(KernelType::Precomputed),
+			_ => Err(format!("Unknown kernel type: {}", val)),
+		}
+	}
+}
+
 /// The parameters needed for certain Kernel types.
 #[derive(Debug,Clone,Copy)]
 pub enum KernelParam {
@@ -26,6 +97,65 @@ impl KernelParam {
 			Precomputed => KernelType::Precomputed,
 		}
 	}
+
+	/// A polynomial kernel, with its parameters checked: the degree must be at least 1 and gamma
+	/// must be positive. Unlike constructing KernelParam::Poly directly, this doesn't allow a gamma
+	/// of 0.0 to be resolved when training.
+	pub fn poly(degree: i32, gamma: f64, coef0: f64) -> Result<KernelParam, ParamError> {
+		if degree < 0 {
+			return Err(ParamError::NegativeDegree(degree));
+		}
+		if degree == 0 {
+			return Err(ParamError::ZeroDegree);
+		}
+		if gamma <= 0.0 || gamma.is_nan() {
+			return Err(ParamError::NonPositiveGamma(gamma));
+		}
+
+		Ok(KernelParam::Poly{degree: degree, gamma: gamma, coef0: coef0})
+	}
+
+	/// Replaces a gamma of exactly 0.0 with 1/num_features, which is how `svm-train` treats
+	/// an unset gamma. Kernels without a gamma, and a num_features of 0, are left alone.
+	pub fn resolve_gamma(&mut self, num_features: i32) {
+		use KernelParam::*;
+		if num_features <= 0 {
+			return;
+		}
+
+		match *self {
+			Poly{ref mut gamma, ..} | Rbf{ref mut gamma} | Sigmoid{ref mut gamma, ..} => {
+				if *gamma == 0.0 {
+					*gamma = 1.0 / num_features as f64;
+				}
+			},
+			Linear | Precomputed => {},
+		}
+	}
+
+	/// Evaluates the kernel function between two vectors using the same formulas as libsvm:
+	///
+	/// * Linear: `a.b`
+	/// * Poly: `(gamma*a.b + coef0)^degree`
+	/// * Rbf: `exp(-gamma*|a-b|^2)`
+	/// * Sigmoid: `tanh(gamma*a.b + coef0)`
+	///
+	/// Precomputed kernels have no function to evaluate (the values are the kernel), so this
+	/// panics if called on Precomputed. The exponential and tanh need the std feature.
+	#[cfg(feature="std")]
+	pub fn evaluate(&self, a: &DataVec, b: &DataVec) -> f64 {
+		use KernelParam::*;
+		match *self {
+			Linear => a.dot(b),
+			Poly{degree, gamma, coef0} => (gamma * a.dot(b) + coef0).powi(degree),
+			Rbf{gamma} => {
+				let sq_dist = a.dot(a) - 2.0 * a.dot(b) + b.dot(b);
+				(-gamma * sq_dist).exp()
+			},
+			Sigmoid{gamma, coef0} => (gamma * a.dot(b) + coef0).tanh(),
+			Precomputed => { panic!("Cannot evaluate a precomputed kernel"); },
+		}
+	}
 }
 
 /// This is a representation of the weights used for CSVC in libsvm.
@@ -38,16 +168,51 @@ pub struct Weight{pub label: i32, pub weight: f64}
 /// are split into nr_weights, weights, and weight_labels, this is
 /// all encoded into a single vector to ensure the lengths match. This will
 /// be converted into the correct lists internally.
+///
+/// Like CSvc, both regression types also need the cost c.
 #[derive(Debug,Clone)]
 pub enum SvmTypeParam {
 	CSvc{c: f64, weights: Vec<Weight>},
     NuSvc{nu: f64},
     OneClass{nu: f64},
-    EpsilonSvr{p: f64},
-    NuSvr{nu: f64},
+    EpsilonSvr{c: f64, p: f64},
+    NuSvr{c: f64, nu: f64},
 }
 
 impl SvmTypeParam {
+	/// Makes a NuSvc, checking that nu is in (0, 1]. Whether nu is actually feasible
+	/// depends on the balance of the classes, which can only be checked against a problem
+	/// with nu_svc_for or SvmProblem::check_parameter.
+	pub fn nu_svc(nu: f64) -> Result<SvmTypeParam, String> {
+		check_nu(nu).map(|_| SvmTypeParam::NuSvc{nu: nu})
+	}
+
+	/// The same as nu_svc, but also checks nu is no more than the problem's max_feasible_nu,
+	/// which svm_train would otherwise reject.
+	#[cfg(feature="std")]
+	pub fn nu_svc_for(nu: f64, prob: &SvmProblem) -> Result<SvmTypeParam, String> {
+		let max_nu = prob.max_feasible_nu();
+		if nu > max_nu {
+			return Err(format!("nu = {} is infeasible for this problem, it can be at most {}", nu, max_nu));
+		}
+
+		SvmTypeParam::nu_svc(nu)
+	}
+
+	/// Makes a OneClass, checking that nu is in (0, 1].
+	pub fn one_class(nu: f64) -> Result<SvmTypeParam, String> {
+		check_nu(nu).map(|_| SvmTypeParam::OneClass{nu: nu})
+	}
+
+	/// Makes a NuSvr, checking that nu is in (0, 1] and c is positive.
+	pub fn nu_svr(c: f64, nu: f64) -> Result<SvmTypeParam, String> {
+		if c <= 0.0 || c.is_nan() {
+			return Err(format!("C must be positive, got {}", c));
+		}
+
+		check_nu(nu).map(|_| SvmTypeParam::NuSvr{c: c, nu: nu})
+	}
+
 	pub fn to_svm_type(&self) -> SvmType {
 		use SvmTypeParam::*;
 		match *self {
@@ -60,7 +225,77 @@ impl SvmTypeParam {
 	}
 }
 
-#[derive(Clone,Debug)]
+/// The problem-independent ways a set of parameters can be out of range, see SvmParameter::validate.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ParamError {
+	/// C must be positive.
+	NonPositiveC(f64),
+	/// nu must be in (0, 1].
+	NuOutOfRange(f64),
+	/// The epsilon-SVR margin p can't be negative.
+	NegativeP(f64),
+	/// The kernel's gamma can't be negative.
+	NegativeGamma(f64),
+	/// The polynomial kernel's degree can't be negative.
+	NegativeDegree(i32),
+	/// A polynomial kernel of degree 0 is a constant, so it can't separate anything.
+	ZeroDegree,
+	/// KernelParam::poly needs an explicit, positive gamma.
+	NonPositiveGamma(f64),
+	/// The cache size (in MB) must be positive.
+	NonPositiveCacheSize(f64),
+	/// The stopping tolerance must be positive.
+	NonPositiveEpsilon(f64),
+	/// libsvm can't estimate probabilities for one-class SVMs.
+	OneClassProbability,
+}
+
+impl fmt::Display for ParamError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			ParamError::NonPositiveC(c) => write!(f, "C must be positive, got {}", c),
+			ParamError::NuOutOfRange(nu) => write!(f, "nu must be in (0, 1], got {}", nu),
+			ParamError::NegativeP(p) => write!(f, "p can't be negative, got {}", p),
+			ParamError::NegativeGamma(gamma) => write!(f, "gamma can't be negative, got {}", gamma),
+			ParamError::NegativeDegree(degree) => write!(f, "degree can't be negative, got {}", degree),
+			ParamError::ZeroDegree => write!(f, "degree must be at least 1"),
+			ParamError::NonPositiveGamma(gamma) => write!(f, "gamma must be positive, got {}", gamma),
+			ParamError::NonPositiveCacheSize(size) => write!(f, "cache_size must be positive, got {}", size),
+			ParamError::NonPositiveEpsilon(eps) => write!(f, "epsilon must be positive, got {}", eps),
+			ParamError::OneClassProbability => write!(f, "one-class SVM probability output not supported yet"),
+		}
+	}
+}
+
+impl Error for ParamError {}
+
+/// The ways svm-train arguments can fail to parse, see SvmParameter::from_args.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ArgsError {
+	/// A flag svm-train doesn't have, or that isn't a parameter (like -v).
+	UnknownFlag(String),
+	/// The last flag had no value after it.
+	MissingValue(String),
+	/// The value given for a flag isn't valid for it.
+	BadValue{flag: String, value: String},
+	/// More arguments than the training set and model file followed the flags.
+	UnexpectedArgument(String),
+}
+
+impl fmt::Display for ArgsError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			ArgsError::UnknownFlag(ref flag) => write!(f, "Unknown flag {}", flag),
+			ArgsError::MissingValue(ref flag) => write!(f, "Missing value for {}", flag),
+			ArgsError::BadValue{ref flag, ref value} => write!(f, "Bad value for {}: {}", flag, value),
+			ArgsError::UnexpectedArgument(ref arg) => write!(f, "Unexpected argument {}", arg),
+		}
+	}
+}
+
+impl Error for ArgsError {}
+
+#[derive(Debug)]
 /// This is a set of parameters for generating a model. It is a Rust representation of the
 /// C struct svm_parameter, and can be converted into a C struct internally. It is built to be more
 /// "Rustic". The C version has many unimportant and unread fields if certain kernel or parameter
@@ -99,8 +334,13 @@ pub struct SvmParameter {
 	// into the newly-made SvmModel, so after svm_train is called, and the only way to view them again involves a clone.
 	// The parameters can never be modified again, and so we "turn off" the recomputation of 
 	// the vectors so we don't invalidate any memory.
+	//
+	// Without std there's no C representation, so these are never read.
+	#[cfg_attr(not(feature="std"), allow(dead_code))]
 	weight_labels: RefCell<Option<Vec<i32>>>,
+	#[cfg_attr(not(feature="std"), allow(dead_code))]
 	weights: RefCell<Option<Vec<f64>>>,
+	#[cfg_attr(not(feature="std"), allow(dead_code))]
 	in_model: bool,
 }
 
@@ -121,11 +361,216 @@ impl SvmParameter {
 		}
 	}
 
-	fn from_crep(crep: &CSvmParameter) -> SvmParameter {
+	/// Parameters for a C-SVC with an RBF kernel, which are the defaults `svm-train` uses:
+	/// C = 1, a 100MB cache, epsilon = 0.001, and shrinking on.
+	///
+	/// If gamma is None, it's left as 0.0, which (like `svm-train`) means it will be resolved to
+	/// 1/num_features once it's trained against a problem.
+	pub fn default_classification(gamma: Option<f64>) -> SvmParameter {
+		SvmParameter::new(KernelParam::Rbf{gamma: gamma.unwrap_or(0.0)},
+			SvmTypeParam::CSvc{c: 1.0, weights: Vec::new()},
+			true, false, 100.0, 0.001)
+	}
+
+	/// Parameters for an epsilon-SVR with an RBF kernel, using the same defaults as
+	/// default_classification, with p = 0.1 (again matching `svm-train`).
+	///
+	/// If gamma is None, it's left as 0.0, which (like `svm-train`) means it will be resolved to
+	/// 1/num_features once it's trained against a problem.
+	pub fn default_regression(gamma: Option<f64>) -> SvmParameter {
+		SvmParameter::new(KernelParam::Rbf{gamma: gamma.unwrap_or(0.0)},
+			SvmTypeParam::EpsilonSvr{c: 1.0, p: 0.1},
+			true, false, 100.0, 0.001)
+	}
+
+	/// Makes a copy of these parameters with a different cost. This does nothing for
+	/// the SVM types that don't use one (NuSvc and OneClass).
+	pub fn with_c(&self, c: f64) -> SvmParameter {
+		use SvmTypeParam::*;
+		let mut param = self.clone();
+		param.in_model = false;
+
+		match param.svm_type_param {
+			CSvc{c: ref mut old_c, ..} | EpsilonSvr{c: ref mut old_c, ..} | NuSvr{c: ref mut old_c, ..} => {
+				*old_c = c;
+			},
+			NuSvc{..} | OneClass{..} => {},
+		}
+
+		param
+	}
+
+	/// Changes the kernel gamma. This does nothing for the kernels that don't use one (Linear
+	/// and Precomputed). It takes the parameters by value so that it can follow with_c without
+	/// a second copy, e.g. `param.with_c(c).with_kernel_gamma(gamma)` in a grid search.
+	pub fn with_kernel_gamma(mut self, gamma: f64) -> SvmParameter {
+		use KernelParam::*;
+		self.in_model = false;
+
+		match self.kernel_param {
+			Poly{gamma: ref mut old_gamma, ..} | Rbf{gamma: ref mut old_gamma} | Sigmoid{gamma: ref mut old_gamma, ..} => {
+				*old_gamma = gamma;
+			},
+			Linear | Precomputed => {},
+		}
+
+		self
+	}
+
+	/// Turns probability estimates on or off, then checks the result with validate. This
+	/// fails if probability estimates are requested for a one-class SVM, which libsvm doesn't
+	/// support, or if any other parameter is invalid.
+	pub fn with_probability(mut self, probability: bool) -> Result<SvmParameter, ParamError> {
+		self.probability = probability;
+		self.validate()?;
+		Ok(self)
+	}
+
+	/// Turns the shrinking heuristic on or off. Unlike with_probability this can't fail,
+	/// since libsvm supports shrinking for every SVM type.
+	pub fn with_shrinking(mut self, shrinking: bool) -> SvmParameter {
+		self.shrinking = shrinking;
+		self
+	}
+
+	/// Performs the range checks of svm_check_parameter that don't need a problem, so e.g.
+	/// user-entered hyperparameters can be checked right away. Whether nu is feasible for
+	/// the class balance still needs SvmProblem::check_parameter.
+	///
+	/// A gamma of 0.0 is allowed, since it's resolved when training.
+	pub fn validate(&self) -> Result<(), ParamError> {
+		use SvmTypeParam::*;
+		use KernelParam::*;
+
+		let positive = |x: f64| x > 0.0 && !x.is_nan();
+
+		match self.svm_type_param {
+			CSvc{c, ..} if !positive(c) => { return Err(ParamError::NonPositiveC(c)); },
+			EpsilonSvr{c, ..} | NuSvr{c, ..} if !positive(c) => { return Err(ParamError::NonPositiveC(c)); },
+			_ => {},
+		}
+		match self.svm_type_param {
+			NuSvc{nu} | OneClass{nu} | NuSvr{nu, ..} if !(positive(nu) && nu <= 1.0) => {
+				return Err(ParamError::NuOutOfRange(nu));
+			},
+			EpsilonSvr{p, ..} if p < 0.0 || p.is_nan() => { return Err(ParamError::NegativeP(p)); },
+			_ => {},
+		}
+
+		match self.kernel_param {
+			Poly{degree, ..} if degree < 0 => { return Err(ParamError::NegativeDegree(degree)); },
+			Poly{degree: 0, ..} => { return Err(ParamError::ZeroDegree); },
+			Poly{gamma, ..} | Rbf{gamma} | Sigmoid{gamma, ..} if gamma < 0.0 || gamma.is_nan() => {
+				return Err(ParamError::NegativeGamma(gamma));
+			},
+			_ => {},
+		}
+
+		if !positive(self.cache_size) {
+			return Err(ParamError::NonPositiveCacheSize(self.cache_size));
+		}
+		if !positive(self.epsilon) {
+			return Err(ParamError::NonPositiveEpsilon(self.epsilon));
+		}
+
+		match self.svm_type_param {
+			OneClass{..} if self.probability => Err(ParamError::OneClassProbability),
+			_ => Ok(()),
+		}
+	}
+
+	/// The `svm-train` flags that give these parameters, e.g. `-s 0 -t 2 -g 0.5 -c 1 ...`.
+	/// Only the flags the SVM and kernel type use are emitted, along with the cache size, epsilon,
+	/// shrinking and probability. A gamma of 0.0 is left out, which `svm-train` resolves the same way
+	/// train does. from_command_line_args (or from_args) parses these back.
+	pub fn to_command_line_args(&self) -> Vec<String> {
+		use KernelParam::*;
+		use SvmTypeParam::*;
+
+		let mut args = Vec::new();
+		{
+			let mut flag = |flag: &str, val: String| {
+				args.push(flag.to_string());
+				args.push(val);
+			};
+
+			flag("-s", (self.svm_type_param.to_svm_type() as i32).to_string());
+			flag("-t", (self.kernel_param.to_kernel_type() as i32).to_string());
+
+			match self.kernel_param {
+				Poly{degree, gamma, coef0} => {
+					flag("-d", degree.to_string());
+					if gamma != 0.0 {
+						flag("-g", gamma.to_string());
+					}
+					flag("-r", coef0.to_string());
+				},
+				Rbf{gamma} if gamma != 0.0 => flag("-g", gamma.to_string()),
+				Sigmoid{gamma, coef0} => {
+					if gamma != 0.0 {
+						flag("-g", gamma.to_string());
+					}
+					flag("-r", coef0.to_string());
+				},
+				Rbf{..} | Linear | Precomputed => {},
+			}
+
+			match self.svm_type_param {
+				CSvc{c, ref weights} => {
+					flag("-c", c.to_string());
+					for w in weights {
+						flag(&format!("-w{}", w.label), w.weight.to_string());
+					}
+				},
+				NuSvc{nu} | OneClass{nu} => flag("-n", nu.to_string()),
+				EpsilonSvr{c, p} => {
+					flag("-c", c.to_string());
+					flag("-p", p.to_string());
+				},
+				NuSvr{c, nu} => {
+					flag("-c", c.to_string());
+					flag("-n", nu.to_string());
+				},
+			}
+
+			flag("-m", self.cache_size.to_string());
+			flag("-e", self.epsilon.to_string());
+			flag("-h", (self.shrinking as i32).to_string());
+			flag("-b", (self.probability as i32).to_string());
+		}
+
+		args
+	}
+
+	/// Parses `svm-train` arguments into parameters, e.g. the split up command
+	/// `svm-train -s 0 -t 2 -c 8 -g 0.125 train.txt`. Anything not given takes `svm-train`'s default,
+	/// so e.g. an empty list gives the same parameters as default_classification(None). Each `-wLABEL`
+	/// adds a CSvc weight.
+	///
+	/// A first argument that isn't a flag is taken to be the program name, and the first argument
+	/// after that which isn't a flag or a flag's value ends the flags, followed by at most one more
+	/// (the training set and model file, which are ignored). `-q` is accepted and ignored, while `-v`
+	/// (cross validation) isn't a parameter and is rejected like any other unknown flag.
+	pub fn from_args(args: &[&str]) -> Result<SvmParameter, ArgsError> {
+		parse_args(args.iter().cloned())
+	}
+
+	/// The same as from_args, for owned arguments such as those of std::env::args.
+	/// This parses what to_command_line_args emits back into the same parameters.
+	pub fn from_command_line_args(args: &[String]) -> Result<SvmParameter, ArgsError> {
+		parse_args(args.iter().map(|arg| &arg[..]))
+	}
+
+	// Fails if the C struct has an SVM or kernel type that isn't one libsvm defines.
+	#[cfg(feature="std")]
+	fn from_crep(crep: &CSvmParameter) -> Result<SvmParameter, String> {
 		use KernelType::*;
 		use SvmType::*;
 
-		SvmParameter {
+		let kernel_type = KernelType::try_from(crep.kernel_type)?;
+		let svm_type = SvmType::try_from(crep.svm_type)?;
+
+		Ok(SvmParameter {
 			in_model: false,
 			weight_labels: RefCell::new(None),
 			weights: RefCell::new(None),
@@ -135,7 +580,7 @@ impl SvmParameter {
 			cache_size: crep.cache_size,
 			epsilon: crep.eps,
 
-			kernel_param: match crep.kernel_type {
+			kernel_param: match kernel_type {
 				Linear => KernelParam::Linear,
 				Poly => KernelParam::Poly{degree: crep.degree, gamma: crep.gamma, coef0: crep.coef0},
 				Rbf => KernelParam::Rbf{gamma: crep.gamma},
@@ -143,19 +588,20 @@ impl SvmParameter {
 				Precomputed => KernelParam::Precomputed,
 			},
 
-			svm_type_param: match crep.svm_type {
+			svm_type_param: match svm_type {
 				CSvc => SvmTypeParam::CSvc{
 					c: crep.c, 
 					weights: make_weights(crep.nr_weight, crep.weight_label, crep.weight)
 				},
 				NuSvc => SvmTypeParam::NuSvc{nu: crep.nu},
 				OneClass => SvmTypeParam::OneClass{nu: crep.nu},
-				EpsilonSvr => SvmTypeParam::EpsilonSvr{p: crep.p},
-				NuSvr => SvmTypeParam::NuSvr{nu: crep.nu},
+				EpsilonSvr => SvmTypeParam::EpsilonSvr{c: crep.c, p: crep.p},
+				NuSvr => SvmTypeParam::NuSvr{c: crep.c, nu: crep.nu},
 			}
-		}
+		})
 	}
 
+	#[cfg(feature="std")]
 	fn crep(&self) -> CSvmParameter {
 		use SvmTypeParam::*;
 		use KernelParam::*;
@@ -166,7 +612,7 @@ impl SvmParameter {
 
 		let mut c_params: CSvmParameter = CSvmParameter::default();
 
-		c_params.kernel_type = self.kernel_param.to_kernel_type();
+		c_params.kernel_type = self.kernel_param.to_kernel_type() as c_int;
 		match self.kernel_param {
 			Poly{degree, gamma, coef0} => {
 				c_params.degree = degree;
@@ -184,7 +630,7 @@ impl SvmParameter {
 			Precomputed => {},
 		};
 
-		c_params.svm_type = self.svm_type_param.to_svm_type();
+		c_params.svm_type = self.svm_type_param.to_svm_type() as c_int;
 		match self.svm_type_param {
 			CSvc{c, ref weights} => {
 				c_params.c = c;
@@ -196,8 +642,14 @@ impl SvmParameter {
 			}
 			NuSvc{nu} => {c_params.nu = nu},
 			OneClass{nu} => {c_params.nu = nu},
-			EpsilonSvr{p} => {c_params.p = p},
-			NuSvr{nu} => {c_params.nu = nu},
+			EpsilonSvr{c, p} => {
+				c_params.c = c;
+				c_params.p = p;
+			},
+			NuSvr{c, nu} => {
+				c_params.c = c;
+				c_params.nu = nu;
+			},
 		};
 
 		c_params.shrinking = self.shrinking as c_int;
@@ -208,11 +660,13 @@ impl SvmParameter {
 		c_params
 	}
 
+	#[cfg(feature="std")]
 	fn invalidate_cache(&self) {
 		*self.weight_labels.borrow_mut() = None;
 		*self.weights.borrow_mut() = None;
 	}
 
+	#[cfg(feature="std")]
 	fn cache_weights(&self, weights: &Vec<Weight>) {
 		if self.weight_labels.borrow().is_some() {
 			return;
@@ -231,6 +685,110 @@ impl SvmParameter {
 	}
 }
 
+// The cached weight vectors are never copied, they're rebuilt the next time
+// the clone is converted into its C representation. This keeps clones (e.g. in
+// parameter sweeps) from allocating anything they may never use.
+impl Clone for SvmParameter {
+	fn clone(&self) -> SvmParameter {
+		SvmParameter {
+			kernel_param: self.kernel_param,
+			svm_type_param: self.svm_type_param.clone(),
+			shrinking: self.shrinking,
+			probability: self.probability,
+			cache_size: self.cache_size,
+			epsilon: self.epsilon,
+			weight_labels: RefCell::new(None),
+			weights: RefCell::new(None),
+			in_model: self.in_model,
+		}
+	}
+}
+
+// Equality and hashing compare floats by their bit patterns, so that they can be used as
+// HashMap keys (e.g. to memoize a parameter sweep). This means parameters only compare equal if
+// they were set to exactly the same values, NaN equals NaN, and 0.0 doesn't equal -0.0.
+impl KernelParam {
+	fn key(&self) -> (KernelType, i32, u64, u64) {
+		use KernelParam::*;
+		match *self {
+			Linear => (KernelType::Linear, 0, 0, 0),
+			Poly{degree, gamma, coef0} => (KernelType::Poly, degree, gamma.to_bits(), coef0.to_bits()),
+			Rbf{gamma} => (KernelType::Rbf, 0, gamma.to_bits(), 0),
+			Sigmoid{gamma, coef0} => (KernelType::Sigmoid, 0, gamma.to_bits(), coef0.to_bits()),
+			Precomputed => (KernelType::Precomputed, 0, 0, 0),
+		}
+	}
+}
+
+impl PartialEq for KernelParam {
+	fn eq(&self, other: &KernelParam) -> bool {
+		self.key() == other.key()
+	}
+}
+
+impl Eq for KernelParam {}
+
+impl Hash for KernelParam {
+	fn hash<H: Hasher>(&self, state: &mut H) {
+		self.key().hash(state)
+	}
+}
+
+impl SvmTypeParam {
+	fn key(&self) -> (SvmType, u64, u64, Vec<(i32, u64)>) {
+		use SvmTypeParam::*;
+		match *self {
+			CSvc{c, ref weights} => (SvmType::CSvc, c.to_bits(), 0,
+				weights.iter().map(|&Weight{label, weight}| (label, weight.to_bits())).collect()),
+			NuSvc{nu} => (SvmType::NuSvc, nu.to_bits(), 0, Vec::new()),
+			OneClass{nu} => (SvmType::OneClass, nu.to_bits(), 0, Vec::new()),
+			EpsilonSvr{c, p} => (SvmType::EpsilonSvr, c.to_bits(), p.to_bits(), Vec::new()),
+			NuSvr{c, nu} => (SvmType::NuSvr, c.to_bits(), nu.to_bits(), Vec::new()),
+		}
+	}
+}
+
+impl PartialEq for SvmTypeParam {
+	fn eq(&self, other: &SvmTypeParam) -> bool {
+		self.key() == other.key()
+	}
+}
+
+impl Eq for SvmTypeParam {}
+
+impl Hash for SvmTypeParam {
+	fn hash<H: Hasher>(&self, state: &mut H) {
+		self.key().hash(state)
+	}
+}
+
+// The weight caches and in_model are bookkeeping for the C representation, and two
+// parameters that would train the same model should be equal regardless of them.
+impl PartialEq for SvmParameter {
+	fn eq(&self, other: &SvmParameter) -> bool {
+		self.kernel_param == other.kernel_param
+			&& self.svm_type_param == other.svm_type_param
+			&& self.shrinking == other.shrinking
+			&& self.probability == other.probability
+			&& self.cache_size.to_bits() == other.cache_size.to_bits()
+			&& self.epsilon.to_bits() == other.epsilon.to_bits()
+	}
+}
+
+impl Eq for SvmParameter {}
+
+impl Hash for SvmParameter {
+	fn hash<H: Hasher>(&self, state: &mut H) {
+		self.kernel_param.hash(state);
+		self.svm_type_param.hash(state);
+		self.shrinking.hash(state);
+		self.probability.hash(state);
+		self.cache_size.to_bits().hash(state);
+		self.epsilon.to_bits().hash(state);
+	}
+}
+
+#[cfg(feature="std")]
 pub mod protected {
 	use super::SvmParameter;
 	use ::ffi::CSvmParameter;
@@ -239,7 +797,7 @@ pub mod protected {
 		param.in_model = val;
 	}
 
-	pub fn param_from_crep(crep: &CSvmParameter) -> SvmParameter {
+	pub fn param_from_crep(crep: &CSvmParameter) -> Result<SvmParameter, String> {
 		SvmParameter::from_crep(crep)
 	}
 
@@ -248,10 +806,104 @@ pub mod protected {
 	}
 }
 
+// The body of from_args. Values are collected first and only assembled into KernelParam and
+// SvmTypeParam at the end, since -s and -t may come after their parameters.
+fn parse_args<'a, I: Iterator<Item=&'a str>>(args: I) -> Result<SvmParameter, ArgsError> {
+	let (mut svm_type, mut kernel_type) = (SvmType::CSvc, KernelType::Rbf);
+	let (mut degree, mut gamma, mut coef0) = (3, 0.0, 0.0);
+	let (mut c, mut nu, mut p) = (1.0, 0.5, 0.1);
+	let (mut cache_size, mut epsilon, mut shrinking, mut probability) = (100.0, 0.001, true, false);
+	let mut weights = Vec::new();
+
+	let mut args = args.peekable();
+	// A leading program name, e.g. a whole `svm-train ...` command split on whitespace
+	if args.peek().filter(|arg| !arg.starts_with('-')).is_some() {
+		args.next();
+	}
+
+	let mut files = Vec::new();
+	while let Some(flag) = args.next() {
+		if flag == "-q" {
+			continue;
+		}
+		if !flag.starts_with('-') {
+			// The training set and model file names end the flags
+			files.push(flag);
+			files.extend(args.by_ref());
+			break;
+		}
+
+		let val = match args.next() {
+			Some(val) => val,
+			None => { return Err(ArgsError::MissingValue(flag.to_string())); },
+		};
+		let bad_value = || ArgsError::BadValue{flag: flag.to_string(), value: val.to_string()};
+		let int = || val.parse::<i32>().map_err(|_| bad_value());
+		let float = || val.parse::<f64>().map_err(|_| bad_value());
+		let switch = || match val {
+			"0" => Ok(false),
+			"1" => Ok(true),
+			_ => Err(bad_value()),
+		};
+
+		match flag {
+			"-s" => { svm_type = SvmType::try_from(int()?).map_err(|_| bad_value())?; },
+			"-t" => { kernel_type = KernelType::try_from(int()?).map_err(|_| bad_value())?; },
+			"-d" => { degree = int()?; },
+			"-g" => { gamma = float()?; },
+			"-r" => { coef0 = float()?; },
+			"-c" => { c = float()?; },
+			"-n" => { nu = float()?; },
+			"-p" => { p = float()?; },
+			"-m" => { cache_size = float()?; },
+			"-e" => { epsilon = float()?; },
+			"-h" => { shrinking = switch()?; },
+			"-b" => { probability = switch()?; },
+			_ if flag.starts_with("-w") => {
+				let label = flag[2..].parse().map_err(|_| ArgsError::UnknownFlag(flag.to_string()))?;
+				weights.push(Weight{label: label, weight: float()?});
+			},
+			_ => { return Err(ArgsError::UnknownFlag(flag.to_string())); },
+		}
+	}
+
+	if let Some(extra) = files.get(2) {
+		return Err(ArgsError::UnexpectedArgument(extra.to_string()));
+	}
+
+	let kernel_param = match kernel_type {
+		KernelType::Linear => KernelParam::Linear,
+		KernelType::Poly => KernelParam::Poly{degree: degree, gamma: gamma, coef0: coef0},
+		KernelType::Rbf => KernelParam::Rbf{gamma: gamma},
+		KernelType::Sigmoid => KernelParam::Sigmoid{gamma: gamma, coef0: coef0},
+		KernelType::Precomputed => KernelParam::Precomputed,
+	};
+	let svm_type_param = match svm_type {
+		SvmType::CSvc => SvmTypeParam::CSvc{c: c, weights: weights},
+		SvmType::NuSvc => SvmTypeParam::NuSvc{nu: nu},
+		SvmType::OneClass => SvmTypeParam::OneClass{nu: nu},
+		SvmType::EpsilonSvr => SvmTypeParam::EpsilonSvr{c: c, p: p},
+		SvmType::NuSvr => SvmTypeParam::NuSvr{c: c, nu: nu},
+	};
+
+	Ok(SvmParameter::new(kernel_param, svm_type_param, shrinking, probability, cache_size, epsilon))
+}
+
+fn check_nu(nu: f64) -> Result<(), String> {
+	if nu > 0.0 && nu <= 1.0 {
+		Ok(())
+	} else {
+		Err(format!("nu must be in (0, 1], got {}", nu))
+	}
+}
+
+// This copies the weights out of the C arrays, so a parameter built by from_crep never
+// references memory owned by a model (or anything else that may be freed later).
+#[cfg(feature="std")]
 fn make_weights(nr_weight: i32, weight_label: *mut i32, weight: *mut f64) -> Vec<Weight> {
 	use std::slice;
 
-	if nr_weight == 0 || weight_label.is_null() || weight.is_null() {
+	if nr_weight <= 0 || weight_label.is_null() || weight.is_null() {
 		Vec::new()
 	} else {
 		unsafe {
@@ -0,0 +1,71 @@
+use ::SvmNode;
+use ::datavec::DataVec;
+#[cfg(feature="std")]
+use ::prob::SvmProblem;
+use alloc::vec::Vec;
+use alloc::string::String;
+use alloc::format;
+
+/// The training data for a precomputed kernel. libsvm expects each training sample to be
+/// a row of the kernel (gram) matrix, with its 1-based sample ID at index 0 and the kernel
+/// values against every training sample at indices 1 through l. Unlike other vectors, none of
+/// the zero values may be left out, since libsvm looks the values up by position.
+pub struct PrecomputedKernel {
+    rows: Vec<DataVec>,
+}
+
+impl PrecomputedKernel {
+    /// Builds the training rows from an l x l gram matrix, where gram[i][j] is the kernel
+    /// value between samples i and j. Returns an error if the matrix isn't square.
+    pub fn from_gram_matrix(gram: &[Vec<f64>]) -> Result<PrecomputedKernel, String> {
+        let l = gram.len();
+        let mut rows = Vec::with_capacity(l);
+
+        for (i, row) in gram.iter().enumerate() {
+            if row.len() != l {
+                return Err(format!("Gram matrix is not square. It has {} rows, but row {} has {} columns",
+                                   l, i, row.len()));
+            }
+
+            rows.push(PrecomputedKernel::make_row((i + 1) as f64, row));
+        }
+
+        Ok(PrecomputedKernel { rows: rows })
+    }
+
+    /// The number of training samples.
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    /// Whether there are no training samples.
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    /// Turns the rows into a training problem with the given labels. As with SvmProblem::new,
+    /// this returns an error if the number of labels doesn't match the number of samples.
+    #[cfg(feature="std")]
+    pub fn into_problem(self, labels: Vec<f64>) -> Result<SvmProblem, String> {
+        SvmProblem::new(self.rows, labels)
+    }
+
+    /// Builds the vector to predict a new sample with, where kernel_values[j] is the kernel
+    /// value between the new sample and training sample j. There must be one value for every training
+    /// sample. libsvm ignores the sample ID of a test vector, so it's set to 0.
+    pub fn test_row(kernel_values: &[f64]) -> DataVec {
+        PrecomputedKernel::make_row(0.0, kernel_values)
+    }
+
+    fn make_row(id: f64, kernel_values: &[f64]) -> DataVec {
+        let mut v = Vec::with_capacity(kernel_values.len() + 2);
+
+        v.push(SvmNode(0, id));
+        for (j, &k) in kernel_values.iter().enumerate() {
+            v.push(SvmNode((j + 1) as i32, k));
+        }
+        v.push(SvmNode(-1, 0.0));
+
+        ::datavec::protected::from_raw(v)
+    }
+}
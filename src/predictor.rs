@@ -0,0 +1,66 @@
+use ::datavec::DataVec;
+use ::model::{self,SvmModel,PredictError};
+
+use std::mem;
+
+/// Predicts with a model over and over without allocating. The buffers libsvm writes the
+/// decision values and probability estimates into are sized once from the model when the
+/// predictor is made, and reused for every call.
+///
+/// This borrows the model the same way PredictionCache does, so the buffers can't
+/// end up the wrong size for it. As on SvmModel, predict_values and predict_probability check
+/// the input for NaN and infinite values, and predict doesn't.
+pub struct Predictor<'a> {
+    model: &'a SvmModel,
+    dec_values: Vec<f64>,
+    prob_estimates: Vec<f64>,
+}
+
+impl<'a> Predictor<'a> {
+    /// Makes a predictor for the model, allocating its buffers.
+    pub fn new(model: &'a SvmModel) -> Predictor<'a> {
+        let prob_estimates = if model.check_probability_model() {
+            vec![0.0; model.get_nr_class() as usize]
+        } else {
+            Vec::new()
+        };
+
+        Predictor {
+            model: model,
+            dec_values: vec![0.0; model.decision_function_count()],
+            prob_estimates: prob_estimates,
+        }
+    }
+
+    /// The same as SvmModel::predict. This goes through svm_predict_values, since svm_predict
+    /// would allocate its own decision value buffer on every call.
+    ///
+    /// x isn't checked for NaN or infinite values, use predict_values for that.
+    pub fn predict(&mut self, x: &DataVec) -> f64 {
+        let buf = mem::take(&mut self.dec_values);
+        let (y, buf) = model::predict_values_unchecked(self.model, x, buf);
+        self.dec_values = buf;
+
+        y
+    }
+
+    /// The same as SvmModel::predict_values, with the decision values borrowed from the
+    /// predictor's buffer. They're overwritten by the next call.
+    pub fn predict_values(&mut self, x: &DataVec) -> Result<(f64, &[f64]), PredictError> {
+        let buf = mem::take(&mut self.dec_values);
+        let (y, buf) = self.model.predict_values(x, Some(buf))?;
+        self.dec_values = buf;
+
+        Ok((y, &self.dec_values))
+    }
+
+    /// The same as SvmModel::predict_probability, with the probabilities borrowed from the
+    /// predictor's buffer. They're overwritten by the next call.
+    pub fn predict_probability(&mut self, x: &DataVec) -> Result<(f64, &[f64]), PredictError> {
+        let buf = mem::take(&mut self.prob_estimates);
+        let (p, buf) = self.model.predict_probability(x, Some(buf))?;
+        self.prob_estimates = buf;
+
+        Ok((p, &self.prob_estimates))
+    }
+}
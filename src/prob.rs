@@ -1,23 +1,190 @@
 extern crate libc;
 
-use ::datavec::DataVec;
+use ::datavec::{DataVec,DataVecError};
 use ::SvmNode;
-use ::ffi::{CSvmProblem,svm_train, svm_check_parameter, svm_cross_validation};
-use ::param::SvmParameter;
+use ::ffi::{CSvmProblem,svm_train, svm_check_parameter, svm_cross_validation, check_libsvm_version};
+use ::param::{SvmParameter,KernelType};
 use ::model::SvmModel;
+use ::label::Label;
+use ::rng::SplitMix;
+use ::timing::{self,Timed};
 use std::ffi::{CStr};
 use self::libc::{c_int};
 use std::cell::RefCell;
+use std::io::BufRead;
+use std::cmp::Ordering;
+use std::fmt;
+use std::error::Error;
+use std::sync::mpsc::{self,RecvTimeoutError};
+use std::panic;
+use std::thread;
+use std::time::Duration;
+use std::iter::{Cloned,Zip};
+use std::slice;
+use std::ptr;
+
+/// The reasons training can fail.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TrainError {
+    /// svm_check_parameter rejected the parameters, with the message it gave.
+    InvalidParameter(String),
+    /// svm_train returned a null model.
+    NullModel,
+    /// The linked libsvm isn't a version whose structs this crate knows, with the reason.
+    UnsupportedLibsvm(String),
+    /// train_with_timeout gave up waiting.
+    Timeout,
+    /// The problem failed SvmProblem::validate.
+    InvalidProblem(ProblemError),
+}
+
+impl fmt::Display for TrainError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TrainError::InvalidParameter(ref msg) => write!(f, "Invalid parameters: {}", msg),
+            TrainError::NullModel => write!(f, "libsvm did not produce a model"),
+            TrainError::UnsupportedLibsvm(ref msg) => write!(f, "{}", msg),
+            TrainError::Timeout => write!(f, "Training did not finish in time"),
+            TrainError::InvalidProblem(ref err) => write!(f, "Invalid problem: {}", err),
+        }
+    }
+}
+
+impl Error for TrainError {}
+
+/// The messages svm_check_parameter can give, see SvmProblem::check_parameter_typed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CheckParameterError {
+    /// svm_type isn't one libsvm knows.
+    UnknownSvmType,
+    /// kernel_type isn't one libsvm knows.
+    UnknownKernelType,
+    /// The kernel's gamma is negative.
+    NegativeGamma,
+    /// The polynomial kernel's degree is negative.
+    NegativeDegree,
+    /// cache_size is zero or negative.
+    NonPositiveCacheSize,
+    /// The stopping tolerance eps is zero or negative.
+    NonPositiveEpsilon,
+    /// The cost C of a C-SVC, epsilon-SVR or nu-SVR is zero or negative.
+    NonPositiveC,
+    /// nu isn't in (0, 1] for a nu-SVC, one-class SVM or nu-SVR.
+    NuOutOfRange,
+    /// The epsilon-SVR's p is negative.
+    NegativeP,
+    /// shrinking is something other than 0 or 1.
+    BadShrinking,
+    /// probability is something other than 0 or 1.
+    BadProbability,
+    /// Probability estimates were requested for a one-class SVM, which libsvm doesn't support.
+    OneClassProbability,
+    /// The parameters are valid, but nu is too large for the balance of the classes.
+    InfeasibleNu,
+    /// A message this version of the wrapper doesn't recognize.
+    Other(String),
+}
+
+impl<'a> From<&'a str> for CheckParameterError {
+    fn from(msg: &'a str) -> CheckParameterError {
+        use self::CheckParameterError::*;
+        match msg {
+            "unknown svm type" => UnknownSvmType,
+            "unknown kernel type" => UnknownKernelType,
+            "gamma < 0" => NegativeGamma,
+            "degree of polynomial kernel < 0" => NegativeDegree,
+            "cache_size <= 0" => NonPositiveCacheSize,
+            "eps <= 0" => NonPositiveEpsilon,
+            "C <= 0" => NonPositiveC,
+            "nu <= 0 or nu > 1" => NuOutOfRange,
+            "p < 0" => NegativeP,
+            "shrinking != 0 and shrinking != 1" => BadShrinking,
+            "probability != 0 and probability != 1" => BadProbability,
+            "one-class SVM probability output not supported yet" => OneClassProbability,
+            "specified nu is infeasible" => InfeasibleNu,
+            other => Other(other.to_string()),
+        }
+    }
+}
+
+/// Displays the original libsvm message.
+impl fmt::Display for CheckParameterError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use self::CheckParameterError::*;
+        let msg = match *self {
+            UnknownSvmType => "unknown svm type",
+            UnknownKernelType => "unknown kernel type",
+            NegativeGamma => "gamma < 0",
+            NegativeDegree => "degree of polynomial kernel < 0",
+            NonPositiveCacheSize => "cache_size <= 0",
+            NonPositiveEpsilon => "eps <= 0",
+            NonPositiveC => "C <= 0",
+            NuOutOfRange => "nu <= 0 or nu > 1",
+            NegativeP => "p < 0",
+            BadShrinking => "shrinking != 0 and shrinking != 1",
+            BadProbability => "probability != 0 and probability != 1",
+            OneClassProbability => "one-class SVM probability output not supported yet",
+            InfeasibleNu => "specified nu is infeasible",
+            Other(ref msg) => msg,
+        };
+
+        write!(f, "{}", msg)
+    }
+}
+
+impl Error for CheckParameterError {}
+
+/// The ways a problem can be unfit to hand to libsvm, see SvmProblem::validate.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ProblemError {
+    /// There aren't as many labels as samples.
+    LengthMismatch{labels: usize, samples: usize},
+    /// The pointers handed to libsvm no longer match the samples and labels.
+    StaleRawData,
+    /// The sample at the index is malformed.
+    BadVector(usize, DataVecError),
+    /// The label of the sample at the index is NaN or infinite.
+    NonFiniteLabel(usize),
+    /// The problem is for a precomputed kernel, but the sample at the index doesn't start with
+    /// the 0:serial_number node libsvm reads the sample's position from (see PrecomputedKernel).
+    MissingSerialNumber(usize),
+    /// SvmProblem::concat was given class labels and regression targets: the problem at the index
+    /// has a different kind of label than the first non-empty problem.
+    MixedLabels(usize),
+}
+
+impl fmt::Display for ProblemError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ProblemError::LengthMismatch{labels, samples} => write!(f, "{} labels for {} samples", labels, samples),
+            ProblemError::StaleRawData => write!(f, "the C representation is out of date"),
+            ProblemError::BadVector(i, ref err) => write!(f, "sample {}: {}", i, err),
+            ProblemError::NonFiniteLabel(i) => write!(f, "sample {} has a label that isn't finite", i),
+            ProblemError::MissingSerialNumber(i) => write!(f, "sample {} has no serial number for the precomputed kernel", i),
+            ProblemError::MixedLabels(i) => write!(f, "problem {} mixes class labels with regression targets", i),
+        }
+    }
+}
+
+impl Error for ProblemError {}
 
 /// This is a Rust wrapper over the C struct CSvmProblem. It represents training
 /// data for generating an SVM model. It holds onto data passed in from Rust and 
 /// constructs the C representation for libsvm.
+///
+/// There are no per-sample (instance) weights. Stock libsvm's svm_train doesn't take any, and the
+/// "libsvm-weights" variant from the LIBSVM Tools page adds them as an extra field of svm_problem,
+/// so it can't be used with these structs. For cost-sensitive learning use class weights
+/// (SvmTypeParam::CSvc's weights), or repeat samples, e.g. with subset.
 pub struct SvmProblem {
     y: Vec<f64>,
     x: Vec<DataVec>,
 
     raw_y: RefCell<*mut f64>,
     raw_x: RefCell<Vec<*mut SvmNode>>,
+
+    // Bookkeeping for the caller only, never part of the C representation
+    ids: Option<Vec<u64>>,
 }
 
 impl SvmProblem {
@@ -45,9 +212,50 @@ impl SvmProblem {
             y: y,
             x: x,
             raw_x: RefCell::new(raw_x),
+            ids: None,
         })
     }
 
+    /// Attaches an ID to every sample, e.g. its row in the source data. The IDs are only kept on
+    /// the Rust side and travel with their samples through shuffle, split, stratified_split, subset,
+    /// the k_folds functions and concat, so predictions on a derived problem can be mapped back to the
+    /// original rows. Returns an error if there isn't exactly one ID per sample.
+    pub fn with_ids(mut self, ids: Vec<u64>) -> Result<SvmProblem, String> {
+        if ids.len() != self.y.len() {
+            return Err(format!("Mismatched number of IDs and samples. IDs: {}, Samples: {}", ids.len(), self.y.len()));
+        }
+
+        self.ids = Some(ids);
+        Ok(self)
+    }
+
+    /// The ID of every sample in order, if with_ids was used.
+    pub fn ids(&self) -> Option<&[u64]> {
+        self.ids.as_ref().map(|ids| &ids[..])
+    }
+
+    /// Builds a problem from (label, feature vector) pairs, e.g. straight out of a
+    /// filtered or mapped data source, without collecting them into separate Vecs first.
+    pub fn from_pairs<I: IntoIterator<Item=(f64, DataVec)>>(pairs: I) -> SvmProblem {
+        let (y, x) = pairs.into_iter().unzip();
+
+        // The lengths always match, so this can't fail
+        SvmProblem::new(x, y).unwrap()
+    }
+
+    /// The same as new, but also checks that no vector has a feature index above dim, and
+    /// returns an error naming the first vector that does. This catches data where some rows were
+    /// prepared with more features than others.
+    pub fn with_dimension(x: Vec<DataVec>, y: Vec<f64>, dim: i32) -> Result<SvmProblem, String> {
+        for (i, v) in x.iter().enumerate() {
+            if let Some(&SvmNode(idx, _)) = v.iter().find(|&&SvmNode(idx, _)| idx > dim) {
+                return Err(format!("Vector {} has feature index {}, which is above the dimension {}", i, idx, dim));
+            }
+        }
+
+        SvmProblem::new(x, y)
+    }
+
     fn crep(&self) -> CSvmProblem {
     	CSvmProblem {
     		l: self.y.len() as i32,
@@ -64,10 +272,430 @@ impl SvmProblem {
 
     /// Provides a read-only view into the training labels
     /// used to construct the problem.
-    pub fn labels(&self) -> &Vec<f64> {
+    pub fn labels(&self) -> &[f64] {
         &self.y
     }
 
+    /// The number of samples in the problem.
+    pub fn len(&self) -> usize {
+        self.y.len()
+    }
+
+    /// Whether the problem has no samples.
+    pub fn is_empty(&self) -> bool {
+        self.y.is_empty()
+    }
+
+    /// The feature vector of the i'th sample, or None if there are fewer than i+1 samples.
+    pub fn sample(&self, i: usize) -> Option<&DataVec> {
+        self.x.get(i)
+    }
+
+    /// Iterates over the (label, feature vector) pairs of every sample, in order.
+    pub fn iter<'a>(&'a self) -> impl Iterator<Item=(f64, &'a DataVec)> + 'a {
+        self.into_iter()
+    }
+
+    /// Reads a problem in the libsvm/SVMLight data format, one sample per line. The input is
+    /// parsed a line at a time with SvmProblemBuilder, so the whole file is never held in memory.
+    /// Any malformed line is reported as an error along with its line number.
+    pub fn from_svmlight_reader<R: BufRead>(reader: R) -> Result<SvmProblem, ParseError> {
+        SvmProblem::read_svmlight(reader, SvmProblemBuilder::new())
+    }
+
+    /// The same as from_svmlight_reader, except any sample with a feature index above
+    /// max_feature_index is rejected as an error.
+    ///
+    /// Each sample's vector is allocated once, at its final size, so memory use is bounded by
+    /// the sparse representation rather than the size of the file.
+    pub fn from_svmlight_reader_limited<R: BufRead>(reader: R, max_feature_index: i32)
+                                                    -> Result<SvmProblem, ParseError> {
+        SvmProblem::read_svmlight(reader, SvmProblemBuilder::new().max_feature_index(max_feature_index))
+    }
+
+    fn read_svmlight<R: BufRead>(mut reader: R, mut builder: SvmProblemBuilder) -> Result<SvmProblem, ParseError> {
+        let mut line = String::new();
+
+        loop {
+            line.clear();
+
+            match reader.read_line(&mut line) {
+                Ok(0) => { break; },
+                Ok(_) => {},
+                Err(err) => {
+                    return Err(ParseError {
+                        line_number: builder.lines_read + 1,
+                        column: 0,
+                        kind: ParseErrorKind::Io(err.to_string()),
+                    });
+                },
+            }
+
+            builder.push_svmlight_line(&line)?;
+        }
+
+        Ok(builder.build())
+    }
+
+    /// Shuffles the samples deterministically according to seed, then splits them into two
+    /// problems. The first will contain `fraction` of the samples (rounded to the nearest
+    /// whole sample), and the second the remainder. This is usually used to make a train/test split.
+    ///
+    /// Panics if fraction is not in [0, 1].
+    pub fn split(self, fraction: f64, seed: u64) -> (SvmProblem, SvmProblem) {
+        assert!((0.0..=1.0).contains(&fraction), "Split fraction must be in [0, 1], got {}", fraction);
+
+        let mut order: Vec<usize> = (0..self.y.len()).collect();
+        SplitMix::new(seed).shuffle(&mut order);
+
+        let n_first = (fraction * order.len() as f64).round() as usize;
+        self.partition(&order, n_first)
+    }
+
+    /// The same as split, except the fraction is applied to each class separately so
+    /// the proportion of every label is (approximately) preserved in both problems. This only makes
+    /// sense for classification problems, since labels are compared for exact equality.
+    ///
+    /// Panics if fraction is not in [0, 1].
+    pub fn stratified_split(self, fraction: f64, seed: u64) -> (SvmProblem, SvmProblem) {
+        assert!((0.0..=1.0).contains(&fraction), "Split fraction must be in [0, 1], got {}", fraction);
+
+        let mut rng = SplitMix::new(seed);
+        let mut first = Vec::new();
+        let mut second = Vec::new();
+
+        for (_, mut indices) in self.class_indices() {
+            rng.shuffle(&mut indices);
+
+            let n_first = (fraction * indices.len() as f64).round() as usize;
+            first.extend_from_slice(&indices[..n_first]);
+            second.extend_from_slice(&indices[n_first..]);
+        }
+
+        // Otherwise each side would be sorted by class
+        rng.shuffle(&mut first);
+        rng.shuffle(&mut second);
+
+        let n_first = first.len();
+        first.extend(second);
+        self.partition(&first, n_first)
+    }
+
+    /// Shuffles the order of the samples, deterministically from seed. Each label stays
+    /// with its feature vector.
+    pub fn shuffle(&mut self, seed: u64) {
+        let mut order: Vec<usize> = (0..self.y.len()).collect();
+        SplitMix::new(seed).shuffle(&mut order);
+        let ids = pick_ids(&self.ids, &order);
+
+        let mut samples: Vec<Option<(DataVec, f64)>> = self.x.drain(..).zip(self.y.drain(..))
+            .map(Some).collect();
+        let (x, y) = order.into_iter().map(|idx| samples[idx].take().unwrap()).unzip();
+
+        // Rebuilt rather than permuted in place so the C view points at the new order
+        *self = SvmProblem::new(x, y).unwrap();
+        self.ids = ids;
+    }
+
+    /// Runs cross validation with an explicit fold for every sample, where fold_assignment[i]
+    /// is the fold of sample i. Unlike cross_validation, which shuffles with C's rand(), this
+    /// is entirely reproducible: for each fold a model is trained on every other sample and
+    /// used to predict the samples in the fold. The predictions are returned in sample order.
+    ///
+    /// A gamma of 0.0 is resolved against the whole problem, the same as cross_validation.
+    /// An error is returned if fold_assignment is the wrong length or any fold's model fails to train.
+    pub fn cross_validation_with_folds(&self, param: &SvmParameter, fold_assignment: &[usize])
+                                       -> Result<Vec<f64>, String> {
+        timing::record(Timed::CrossValidation, || self.run_folds(param, fold_assignment, |_, _| {}))
+    }
+
+    /// Runs nr_fold cross validation a fold at a time in Rust, calling on_fold with
+    /// (fold_index, total_folds) after each fold completes, e.g. to drive a progress bar.
+    /// Each class is dealt out across the folds in sample order, so the folds are stratified like
+    /// cross_validation's, but without its random shuffle. For any other split use
+    /// cross_validation_with_folds directly.
+    ///
+    /// As with svm_cross_validation, nr_fold is capped at the number of samples. An error
+    /// is returned if it's less than 2 or any fold's model fails to train.
+    pub fn cross_validation_with_progress<F: FnMut(usize, usize)>(&self, param: &SvmParameter, nr_fold: i32, on_fold: F)
+                                                                 -> Result<Vec<f64>, String> {
+        if nr_fold < 2 {
+            return Err(format!("Number of folds must be at least 2, got {}", nr_fold));
+        }
+        let nr_fold = (nr_fold as usize).min(self.y.len());
+
+        let mut fold_assignment = vec![0; self.y.len()];
+        let mut next = 0;
+        for (_, indices) in self.class_indices() {
+            for i in indices {
+                fold_assignment[i] = next % nr_fold;
+                next += 1;
+            }
+        }
+
+        timing::record(Timed::CrossValidation, || self.run_folds(param, &fold_assignment, on_fold))
+    }
+
+    fn run_folds<F: FnMut(usize, usize)>(&self, param: &SvmParameter, fold_assignment: &[usize], mut on_fold: F)
+                                       -> Result<Vec<f64>, String> {
+        if fold_assignment.len() != self.y.len() {
+            return Err(format!("Mismatched number of fold assignments and samples. Assignments: {}, Samples: {}",
+                               fold_assignment.len(), self.y.len()));
+        }
+
+        let mut param = param.clone();
+        param.kernel_param.resolve_gamma(self.max_feature_index());
+
+        let mut folds: Vec<usize> = fold_assignment.to_vec();
+        folds.sort();
+        folds.dedup();
+
+        let mut predictions = vec![0.0; self.y.len()];
+        for (n, &fold) in folds.iter().enumerate() {
+            let train = self.select((0..self.y.len()).filter(|&i| fold_assignment[i] != fold));
+
+            let model = match train.train(param.clone()) {
+                Ok(model) => model,
+                Err(err) => { return Err(format!("Fold {}: {}", fold, err)); },
+            };
+
+            for i in (0..self.y.len()).filter(|&i| fold_assignment[i] == fold) {
+                predictions[i] = model.predict(&self.x[i]);
+            }
+
+            on_fold(n, folds.len());
+        }
+
+        Ok(predictions)
+    }
+
+    /// Searches a grid of costs and kernel gammas with nr_fold cross validation. Every combination
+    /// of a c from cs and a gamma from gammas is applied to a copy of param (with SvmParameter::with_c
+    /// and with_kernel_gamma), and the cross validation accuracy is returned for each as (c, gamma, accuracy),
+    /// in the order of cs then gammas. Since this scores by accuracy it is meant for classification.
+    ///
+    /// With the `rayon` feature, the grid points are cross validated in parallel, each thread
+    /// working on its own copy of the problem.
+    pub fn grid_search(&self, param: &SvmParameter, cs: &[f64], gammas: &[f64], nr_fold: i32)
+                       -> Vec<(f64, f64, f64)> {
+        let points: Vec<(f64, f64, SvmParameter)> = cs.iter()
+            .flat_map(|&c| gammas.iter().map(move |&gamma| (c, gamma)))
+            .map(|(c, gamma)| (c, gamma, param.with_c(c).with_kernel_gamma(gamma)))
+            .collect();
+
+        self.search_points(points, nr_fold)
+    }
+
+    #[cfg(not(feature="rayon"))]
+    fn search_points(&self, points: Vec<(f64, f64, SvmParameter)>, nr_fold: i32) -> Vec<(f64, f64, f64)> {
+        points.into_iter()
+            .map(|(c, gamma, param)| (c, gamma, self.cross_validation_accuracy(&param, nr_fold)))
+            .collect()
+    }
+
+    #[cfg(feature="rayon")]
+    fn search_points(&self, points: Vec<(f64, f64, SvmParameter)>, nr_fold: i32) -> Vec<(f64, f64, f64)> {
+        use ::rayon::prelude::*;
+
+        // The problem itself can't be shared between threads (it hands out raw pointers
+        // through RefCells), but its data can, so each thread builds its own copy.
+        let (x, y) = (&self.x, &self.y);
+        points.into_par_iter()
+            .map_init(|| SvmProblem::new(x.clone(), y.clone()).unwrap(),
+                      |prob, (c, gamma, param)| (c, gamma, prob.cross_validation_accuracy(&param, nr_fold)))
+            .collect()
+    }
+
+    fn cross_validation_accuracy(&self, param: &SvmParameter, nr_fold: i32) -> f64 {
+        let predictions = self.cross_validation(param, nr_fold, None);
+        let correct = predictions.iter().zip(self.y.iter()).filter(|&(p, y)| p == y).count();
+
+        correct as f64 / self.y.len() as f64
+    }
+
+    /// Deterministically partitions the samples into k folds according to seed, and returns
+    /// a (train, validation) pair for each fold, where the validation problem is the fold itself
+    /// and the train problem is every other sample. Unlike cross_validation, this exposes
+    /// the partitioning so arbitrary per-fold work can be done. Each returned problem is an independent
+    /// copy and can be trained on its own.
+    ///
+    /// Panics if k is less than 2 or greater than the number of samples.
+    pub fn k_folds(&self, k: usize, seed: u64) -> Vec<(SvmProblem, SvmProblem)> {
+        self.check_folds(k);
+
+        let mut order: Vec<usize> = (0..self.y.len()).collect();
+        SplitMix::new(seed).shuffle(&mut order);
+
+        self.make_folds(k, &order)
+    }
+
+    /// The same as k_folds, except each class is dealt out across the folds separately
+    /// so every fold has (approximately) the same label proportions as the whole problem.
+    ///
+    /// Panics if k is less than 2 or greater than the number of samples.
+    pub fn stratified_k_folds(&self, k: usize, seed: u64) -> Vec<(SvmProblem, SvmProblem)> {
+        self.check_folds(k);
+
+        let mut rng = SplitMix::new(seed);
+        let mut order = Vec::with_capacity(self.y.len());
+        for (_, mut indices) in self.class_indices() {
+            rng.shuffle(&mut indices);
+            order.extend(indices);
+        }
+
+        self.make_folds(k, &order)
+    }
+
+    fn check_folds(&self, k: usize) {
+        assert!(k >= 2 && k <= self.y.len(),
+                "Number of folds must be between 2 and the number of samples ({}), got {}", self.y.len(), k);
+    }
+
+    /// Deals the samples in order out to k folds round-robin, and builds the
+    /// (train, validation) problems for each.
+    fn make_folds(&self, k: usize, order: &[usize]) -> Vec<(SvmProblem, SvmProblem)> {
+        (0..k).map(|fold| {
+            let train = order.iter().enumerate().filter(|&(i, _)| i % k != fold).map(|(_, &idx)| idx);
+            let validation = order.iter().enumerate().filter(|&(i, _)| i % k == fold).map(|(_, &idx)| idx);
+
+            (self.select(train), self.select(validation))
+        }).collect()
+    }
+
+    /// Builds a new, independent problem out of copies of the samples at indices, in that order.
+    /// Indices may repeat, e.g. for a bootstrap sample.
+    ///
+    /// Panics if any index is out of range.
+    pub fn subset(&self, indices: &[usize]) -> SvmProblem {
+        if let Some(&i) = indices.iter().find(|&&i| i >= self.y.len()) {
+            panic!("Sample index {} is out of range for a problem with {} samples", i, self.y.len());
+        }
+
+        self.select(indices.iter().cloned())
+    }
+
+    /// Builds a new, independent problem out of copies of all the samples of problems, in order.
+    /// The problems are assumed to share one feature index space. The samples keep their IDs
+    /// if every problem has them.
+    ///
+    /// The labels are copied as they are, and every problem's labels must be the same kind of
+    /// Label. libsvm stores both kinds as f64s, so a problem whose labels are all whole numbers is
+    /// read as Label::Class and any other as Label::Target. This returns ProblemError::MixedLabels
+    /// for the first problem whose kind differs from the first non-empty problem's.
+    pub fn concat(problems: &[SvmProblem]) -> Result<SvmProblem, ProblemError> {
+        let is_classes = |prob: &SvmProblem| prob.y.iter().all(|&y| read_label(y).class().is_some());
+
+        let mut non_empty = problems.iter().enumerate().filter(|&(_, prob)| !prob.is_empty());
+        if let Some((_, first)) = non_empty.next() {
+            let classes = is_classes(first);
+            if let Some((i, _)) = non_empty.find(|&(_, prob)| is_classes(prob) != classes) {
+                return Err(ProblemError::MixedLabels(i));
+            }
+        }
+
+        let len = problems.iter().map(|prob| prob.len()).sum();
+        let mut x = Vec::with_capacity(len);
+        let mut y = Vec::with_capacity(len);
+        for prob in problems {
+            x.extend(prob.x.iter().cloned());
+            y.extend_from_slice(&prob.y);
+        }
+
+        // IDs are only kept if every problem has them, since half of them couldn't be told apart
+        let ids = if problems.iter().all(|prob| prob.ids.is_some()) {
+            Some(problems.iter().flat_map(|prob| prob.ids().unwrap().iter().cloned()).collect())
+        } else {
+            None
+        };
+
+        let mut prob = SvmProblem::new(x, y).unwrap();
+        prob.ids = ids;
+        Ok(prob)
+    }
+
+    /// Builds a new problem out of copies of the samples at the given indices.
+    fn select<I: Iterator<Item=usize>>(&self, indices: I) -> SvmProblem {
+        let indices: Vec<usize> = indices.collect();
+        let (x, y) = indices.iter().map(|&i| (self.x[i].clone(), self.y[i])).unzip();
+
+        let mut prob = SvmProblem::new(x, y).unwrap();
+        prob.ids = pick_ids(&self.ids, &indices);
+        prob
+    }
+
+    /// Each distinct label in the problem along with how many samples have it, sorted by label.
+    pub fn class_distribution(&self) -> Vec<(f64, usize)> {
+        let mut dist: Vec<(f64, usize)> = self.class_indices().into_iter()
+            .map(|(label, indices)| (label, indices.len()))
+            .collect();
+
+        dist.sort_by(|&(a, _), &(b, _)| a.partial_cmp(&b).unwrap_or(Ordering::Equal));
+        dist
+    }
+
+    /// The largest nu a NuSvc can be trained with on this problem. For every pair of classes
+    /// with n1 and n2 samples, libsvm requires nu*(n1+n2)/2 <= min(n1, n2), so this is the smallest
+    /// 2*min(n1, n2)/(n1+n2) over all pairs. With fewer than two classes this is 1.0.
+    pub fn max_feasible_nu(&self) -> f64 {
+        let dist = self.class_distribution();
+        let mut max_nu: f64 = 1.0;
+
+        for (i, &(_, n1)) in dist.iter().enumerate() {
+            for &(_, n2) in &dist[i+1..] {
+                max_nu = max_nu.min(2.0 * n1.min(n2) as f64 / (n1 + n2) as f64);
+            }
+        }
+
+        max_nu
+    }
+
+    /// Groups sample indices by label, in order of each label's first appearance.
+    fn class_indices(&self) -> Vec<(f64, Vec<usize>)> {
+        let mut classes: Vec<(f64, Vec<usize>)> = Vec::new();
+
+        for (i, &label) in self.y.iter().enumerate() {
+            match classes.iter_mut().position(|&mut (l, _)| l == label) {
+                Some(pos) => classes[pos].1.push(i),
+                None => classes.push((label, vec![i])),
+            }
+        }
+
+        classes
+    }
+
+    /// Moves the samples into two new problems, the first made of the samples
+    /// at order[..n_first] and the second of order[n_first..]. Order must be a permutation
+    /// of the sample indices.
+    fn partition(self, order: &[usize], n_first: usize) -> (SvmProblem, SvmProblem) {
+        let first_ids = pick_ids(&self.ids, &order[..n_first]);
+        let second_ids = pick_ids(&self.ids, &order[n_first..]);
+
+        let mut samples: Vec<Option<(DataVec, f64)>> = self.x.into_iter().zip(self.y)
+            .map(Some).collect();
+
+        let (mut first_x, mut first_y) = (Vec::with_capacity(n_first), Vec::with_capacity(n_first));
+        let (mut second_x, mut second_y) = (Vec::new(), Vec::new());
+
+        for (i, &idx) in order.iter().enumerate() {
+            let (x, y) = samples[idx].take().expect("Sample used twice in partition");
+
+            if i < n_first {
+                first_x.push(x);
+                first_y.push(y);
+            } else {
+                second_x.push(x);
+                second_y.push(y);
+            }
+        }
+
+        let mut first = SvmProblem::new(first_x, first_y).unwrap();
+        let mut second = SvmProblem::new(second_x, second_y).unwrap();
+        first.ids = first_ids;
+        second.ids = second_ids;
+
+        (first, second)
+    }
+
     /// Equivalent to svm_check_paramter. It determines whether the given parameters
     /// are within the feasible range for the problem. This should be checked before cross_validation
     /// or train. 
@@ -86,28 +714,203 @@ impl SvmProblem {
         }
     }
 
+    /// The same as check_parameter, but with the message parsed into a CheckParameterError
+    /// so the problem can be matched on.
+    pub fn check_parameter_typed(&self, param: &SvmParameter) -> Result<(), CheckParameterError> {
+        self.check_parameter(param).map_err(CheckParameterError::from)
+    }
+
     /// Trains a model based on the parameters and problem.
     /// Since the trained model may reference memory from the parameters
     /// or problem, this takes ownership of both values. It's recommended you
     /// clone these values if you have need of them.
-    pub fn train<'a>(self, param: SvmParameter) -> SvmModel<'a> {
+    ///
+    /// As with `svm-train`, if the kernel's gamma is 0.0 it is replaced with 1/num_features,
+    /// where num_features is the problem's max_feature_index. The resolved gamma can be seen
+    /// through the model's view_params.
+    ///
+    /// The parameters are checked with check_parameter first, and an error is returned if they're
+    /// infeasible (or if libsvm fails to produce a model anyway) rather than training garbage.
+    pub fn train(self, mut param: SvmParameter) -> Result<SvmModel, TrainError> {
         use ::model::model_from_c_rep;
         use ::param::protected::crep;
+
+        if let Err(err) = check_libsvm_version() {
+            return Err(TrainError::UnsupportedLibsvm(err));
+        }
+        if let Err(err) = self.validate(param.kernel_param.to_kernel_type()) {
+            return Err(TrainError::InvalidProblem(err));
+        }
+
+        param.kernel_param.resolve_gamma(self.max_feature_index());
+        if let Err(err) = self.check_parameter(&param) {
+            return Err(TrainError::InvalidParameter(err.to_string()));
+        }
+
         unsafe {
-        	model_from_c_rep(&mut (*svm_train(&self.crep(), &crep(&param))), self, param)
+            let model = timing::record(Timed::Train, || svm_train(&self.crep(), &crep(&param)));
+            if model.is_null() {
+                return Err(TrainError::NullModel);
+            }
+
+        	Ok(model_from_c_rep(model, self, param))
+        }
+    }
+
+    /// The same as train, but gives up and returns TrainError::Timeout if no model has been
+    /// produced after timeout. libsvm has no way to interrupt svm_train, so training is run on a
+    /// new thread, and on a timeout that thread is abandoned rather than stopped: it keeps running
+    /// (and using a core) until svm_train finishes, and its model is then freed.
+    ///
+    /// If training panics, the panic is resumed on this thread. With the timing feature, a
+    /// successful training's duration is recorded for this thread's last_train_duration.
+    pub fn train_with_timeout(self, param: SvmParameter, timeout: Duration) -> Result<SvmModel, TrainError> {
+        // The model isn't Send because of its raw pointers, but it's only ever moved whole
+        // from the training thread to this one, along with the problem and parameters it owns.
+        struct SendModel(SvmModel);
+        unsafe impl Send for SendModel {}
+
+        let (sender, receiver) = mpsc::channel();
+        let (x, y, ids) = (self.x, self.y, self.ids);
+
+        let trainer = thread::spawn(move || {
+            let mut prob = SvmProblem::new(x, y).unwrap();
+            prob.ids = ids;
+
+            let result = prob.train(param).map(SendModel);
+            // The receiver is gone if we timed out, in which case the model is just dropped
+            let _ = sender.send((result, timing::last(Timed::Train)));
+        });
+
+        match receiver.recv_timeout(timeout) {
+            Ok((result, measured)) => {
+                timing::set_last(Timed::Train, measured);
+                result.map(|SendModel(model)| model)
+            },
+            Err(RecvTimeoutError::Timeout) => Err(TrainError::Timeout),
+            // The sender can only be dropped without sending if training panicked
+            Err(RecvTimeoutError::Disconnected) => panic::resume_unwind(trainer.join().unwrap_err()),
+        }
+    }
+
+    /// Checks everything train relies on when handing the problem to libsvm: that there's a
+    /// label for every sample, that the C representation still points at them, that every vector
+    /// passes DataVec::validate and validate_finite, and that the labels are finite. train calls this
+    /// first, with the kernel of its parameters.
+    ///
+    /// Only a precomputed kernel's rows may have a 0 index, and they all need it: each has to start
+    /// with the sample's serial number, as PrecomputedKernel builds them.
+    pub fn validate(&self, kernel: KernelType) -> Result<(), ProblemError> {
+        if self.y.len() != self.x.len() {
+            return Err(ProblemError::LengthMismatch{labels: self.y.len(), samples: self.x.len()});
+        }
+
+        let raw_x = self.raw_x.borrow();
+        let fresh = ptr::eq(*self.raw_y.borrow(), self.y.as_ptr())
+            && raw_x.len() == self.x.len()
+            && raw_x.iter().zip(&self.x).all(|(&raw, v)| ptr::eq(raw, v.as_ptr()));
+        if !fresh {
+            return Err(ProblemError::StaleRawData);
+        }
+
+        for (i, v) in self.x.iter().enumerate() {
+            if let Err(err) = v.validate().and_then(|_| v.validate_finite()) {
+                return Err(ProblemError::BadVector(i, err));
+            }
+
+            match (kernel, v[0].0) {
+                (KernelType::Precomputed, 0) => {},
+                (KernelType::Precomputed, _) => { return Err(ProblemError::MissingSerialNumber(i)); },
+                (_, 0) => { return Err(ProblemError::BadVector(i, DataVecError::BadIndex{position: 0, index: 0})); },
+                _ => {},
+            }
+        }
+
+        match self.y.iter().position(|y| !y.is_finite()) {
+            Some(i) => Err(ProblemError::NonFiniteLabel(i)),
+            None => Ok(()),
+        }
+    }
+
+    /// The mean DataVec::density of the samples, or 0.0 if there are none. Since each vector's density
+    /// is relative to its own highest index, compare with max_feature_index to see how sparse the
+    /// problem is as a whole.
+    pub fn average_density(&self) -> f64 {
+        if self.x.is_empty() {
+            return 0.0;
+        }
+
+        self.x.iter().map(|v| v.density()).sum::<f64>() / self.x.len() as f64
+    }
+
+    /// The highest feature index used by any of the problem's vectors, or 0 if every vector is empty.
+    pub fn max_feature_index(&self) -> i32 {
+        self.x.iter()
+            .flat_map(|v| v.iter().map(|&SvmNode(idx, _)| idx))
+            .max()
+            .map_or(0, |idx| if idx < 0 { 0 } else { idx })
+    }
+
+    /// Estimates a reasonable gamma for an RBF kernel with the median heuristic. This picks
+    /// sample_size random pairs of distinct samples (deterministically from seed), and returns
+    /// 1/(2*m), where m is the median squared distance between the pairs.
+    ///
+    /// If there are fewer than two samples, sample_size is 0, or the median distance is 0, this
+    /// falls back to `svm-train`'s default of 1/max_feature_index (or 1.0 if there are no features).
+    pub fn estimate_rbf_gamma(&self, sample_size: usize, seed: u64) -> f64 {
+        let n = self.x.len();
+        let fallback = match self.max_feature_index() {
+            0 => 1.0,
+            max => 1.0 / max as f64,
+        };
+
+        if n < 2 || sample_size == 0 {
+            return fallback;
+        }
+
+        let mut rng = SplitMix::new(seed);
+        let mut sq_dists: Vec<f64> = (0..sample_size).map(|_| {
+            let i = rng.below(n);
+            // Offsetting by 1..n guarantees a different sample
+            let j = (i + 1 + rng.below(n - 1)) % n;
+
+            let (a, b) = (&self.x[i], &self.x[j]);
+            a.dot(a) - 2.0 * a.dot(b) + b.dot(b)
+        }).collect();
+
+        sq_dists.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+
+        let mid = sq_dists.len() / 2;
+        let median = if sq_dists.len().is_multiple_of(2) {
+            (sq_dists[mid - 1] + sq_dists[mid]) / 2.0
+        } else {
+            sq_dists[mid]
+        };
+
+        if median > 0.0 {
+            1.0 / (2.0 * median)
+        } else {
+            fallback
         }
     }
 
     /// Runs cross validation for nr_fold folds on the given parameters and problem.
+    /// A gamma of 0.0 is resolved the same way as in train.
     /// If labels is supplied, it will be filled with the labels generated by the cross validation,
     /// otherwise a new one will be allocated for you based on the number of problems in the
     /// training set. Either way, this vector is returned.
+    ///
+    /// Panics if the linked libsvm isn't a supported version, see TrainError::UnsupportedLibsvm.
     pub fn cross_validation(&self,
                             param: &SvmParameter,
                             nr_fold: i32,
                             labels: Option<Vec<f64>>)
                             -> Vec<f64> {
         use ::param::protected::crep;
+        if let Err(err) = check_libsvm_version() {
+            panic!("{}", err);
+        }
+
         let mut labels = match labels {
             None => {
                 let mut labels = Vec::with_capacity(self.y.len());
@@ -120,16 +923,256 @@ impl SvmProblem {
             Some(labels) => labels,
         };
 
-        unsafe {
-            svm_cross_validation(&self.crep(), &crep(param), nr_fold as c_int, labels.as_mut_ptr());
-        }
+        let mut param = param.clone();
+        param.kernel_param.resolve_gamma(self.max_feature_index());
+
+        timing::record(Timed::CrossValidation, || unsafe {
+            svm_cross_validation(&self.crep(), &crep(&param), nr_fold as c_int, labels.as_mut_ptr());
+        });
 
         labels
     }
 }
 
+/// An error in a line of libsvm/SVMLight formatted data.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseError {
+    /// The line the error is on, starting at 1. This is 0 if the input couldn't be opened at all.
+    pub line_number: usize,
+    /// The column (in characters, starting at 1) of the offending part of the line.
+    /// This is 0 for errors that aren't about a particular part of the line.
+    pub column: usize,
+    pub kind: ParseErrorKind,
+}
+
+/// What went wrong parsing a line. Each variant holds the offending text where there is some.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    /// A feature has no colon separating the index and value.
+    MissingColon(String),
+    /// A feature index isn't an integer, or is less than 1.
+    BadIndex(String),
+    /// A feature value isn't a number.
+    BadValue(String),
+    /// The label isn't a number.
+    BadLabel(String),
+    /// A feature index is above the limit set on the builder.
+    IndexAboveMax{index: i32, max: i32},
+    /// A feature index isn't above the one before it on the line, so the features are out of
+    /// order or the index is repeated.
+    IndexOutOfOrder{index: i32, previous: i32},
+    /// The line couldn't be read.
+    Io(String),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.line_number > 0 {
+            write!(f, "line {}: ", self.line_number)?;
+        }
+
+        match self.kind {
+            ParseErrorKind::MissingColon(ref tok) => write!(f, "missing colon in \"{}\"", tok),
+            ParseErrorKind::BadIndex(ref tok) => write!(f, "bad index \"{}\"", tok),
+            ParseErrorKind::BadValue(ref tok) => write!(f, "bad value \"{}\"", tok),
+            ParseErrorKind::BadLabel(ref tok) => write!(f, "bad label \"{}\"", tok),
+            ParseErrorKind::IndexAboveMax{index, max} => write!(f, "index {} is above the maximum of {}", index, max),
+            ParseErrorKind::IndexOutOfOrder{index, previous} => {
+                write!(f, "index {} is not above the previous index {}", index, previous)
+            },
+            ParseErrorKind::Io(ref msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl Error for ParseError {}
+
+// How a label reads without a model to say what it is: a whole number that fits in an i32 is a
+// class, anything else a regression target.
+fn read_label(y: f64) -> Label {
+    if y.fract() == 0.0 && y >= i32::MIN as f64 && y <= i32::MAX as f64 {
+        Label::Class(y as i32)
+    } else {
+        Label::Target(y)
+    }
+}
+
+// The IDs of the samples at indices, in that order, if there are any.
+fn pick_ids(ids: &Option<Vec<u64>>, indices: &[usize]) -> Option<Vec<u64>> {
+    ids.as_ref().map(|ids| indices.iter().map(|&i| ids[i]).collect())
+}
+
+/// Builds an SvmProblem one sample at a time, so a problem can be made from a streaming
+/// source (such as a large data file read line by line) without holding anything more than
+/// the sparse vectors themselves in memory.
+pub struct SvmProblemBuilder {
+    x: Vec<DataVec>,
+    y: Vec<f64>,
+
+    max_feature_index: Option<i32>,
+    lines_read: usize,
+}
+
+impl SvmProblemBuilder {
+    /// Makes an empty builder with no limit on feature indices.
+    pub fn new() -> SvmProblemBuilder {
+        SvmProblemBuilder::with_capacity(0)
+    }
+
+    /// Makes an empty builder with room for n samples before it has to reallocate.
+    pub fn with_capacity(n: usize) -> SvmProblemBuilder {
+        SvmProblemBuilder {
+            x: Vec::with_capacity(n),
+            y: Vec::with_capacity(n),
+            max_feature_index: None,
+            lines_read: 0,
+        }
+    }
+
+    /// Limits the feature indices of pushed samples to max_feature_index. Any sample
+    /// with a higher index will be rejected.
+    pub fn max_feature_index(mut self, max_feature_index: i32) -> SvmProblemBuilder {
+        self.max_feature_index = Some(max_feature_index);
+        self
+    }
+
+    /// The number of samples pushed so far.
+    pub fn len(&self) -> usize {
+        self.y.len()
+    }
+
+    /// Whether no samples have been pushed yet.
+    pub fn is_empty(&self) -> bool {
+        self.y.is_empty()
+    }
+
+    /// Adds a sample with the given label, or returns an error if it has a feature index
+    /// above the limit.
+    pub fn push(&mut self, mut x: DataVec, y: f64) -> Result<(), String> {
+        if let Some(max) = self.max_feature_index {
+            if let Some(&SvmNode(idx, _)) = x.iter().find(|&&SvmNode(idx, _)| idx > max) {
+                return Err(format!("Feature index {} is above the maximum of {}", idx, max));
+            }
+        }
+
+        x.resort();
+        x.shrink_to_fit();
+
+        self.x.push(x);
+        self.y.push(y);
+        Ok(())
+    }
+
+    /// The same as push, with the label given as a class or a regression target.
+    pub fn push_label<L: Into<Label>>(&mut self, x: DataVec, y: L) -> Result<(), String> {
+        self.push(x, y.into().as_f64())
+    }
+
+    /// Parses a line in the libsvm/SVMLight data format (`label index:value index:value ...`)
+    /// and adds it as a sample. Anything after a `#` is treated as a comment, and blank
+    /// lines are ignored. As in that format, the indices on a line must be in ascending order.
+    ///
+    /// The builder counts every line passed to this, so when lines are fed in from a file
+    /// the errors will have the right line number.
+    pub fn push_svmlight_line(&mut self, full_line: &str) -> Result<(), ParseError> {
+        use ::datavec::protected::parse_features;
+        use ::datavec::ParseDataVecError;
+
+        self.lines_read += 1;
+        let line_number = self.lines_read;
+        let error = |part: &str, kind: ParseErrorKind| ParseError {
+            line_number: line_number,
+            column: full_line[..(part.as_ptr() as usize - full_line.as_ptr() as usize)].chars().count() + 1,
+            kind: kind,
+        };
+
+        let line = match full_line.find('#') {
+            Some(pos) => &full_line[..pos],
+            None => full_line,
+        }.trim();
+
+        if line.is_empty() {
+            return Ok(());
+        }
+
+        let (label, feature_str) = match line.find(char::is_whitespace) {
+            Some(pos) => (&line[..pos], &line[pos..]),
+            None => (line, ""),
+        };
+
+        let y = match label.parse::<f64>() {
+            Ok(y) => y,
+            Err(_) => { return Err(error(label, ParseErrorKind::BadLabel(label.to_string()))); },
+        };
+
+        let features = match parse_features(feature_str) {
+            Ok(features) => features,
+            Err((offset, err)) => {
+                let part = &feature_str[offset..];
+                let part = &part[..part.find(char::is_whitespace).unwrap_or(part.len())];
+
+                let kind = match err {
+                    ParseDataVecError::MissingColon(_) => ParseErrorKind::MissingColon(part.to_string()),
+                    ParseDataVecError::BadIndex(_) => {
+                        ParseErrorKind::BadIndex(part[..part.find(':').unwrap_or(part.len())].to_string())
+                    },
+                    ParseDataVecError::BadValue(_) => ParseErrorKind::BadValue(part.to_string()),
+                };
+
+                return Err(error(part, kind));
+            },
+        };
+
+        let mut previous = 0;
+        for &(offset, SvmNode(idx, _)) in &features {
+            if idx <= previous {
+                return Err(error(&feature_str[offset..], ParseErrorKind::IndexOutOfOrder{index: idx, previous: previous}));
+            }
+            previous = idx;
+        }
+
+        if let Some(max) = self.max_feature_index {
+            if let Some(&(offset, SvmNode(idx, _))) = features.iter().find(|&&(_, SvmNode(idx, _))| idx > max) {
+                return Err(error(&feature_str[offset..], ParseErrorKind::IndexAboveMax{index: idx, max: max}));
+            }
+        }
+
+        // With room for the terminator, the vector is allocated once at its final size
+        let mut nodes = Vec::with_capacity(features.len() + 1);
+        nodes.extend(features.into_iter().map(|(_, node)| node));
+
+        let x = DataVec::from_sparse(nodes);
+        // The limit was already checked above, so this can't fail
+        self.push(x, y).unwrap();
+        Ok(())
+    }
+
+    /// Finishes building the problem.
+    pub fn build(self) -> SvmProblem {
+        SvmProblem::new(self.x, self.y).unwrap()
+    }
+}
+
+impl Default for SvmProblemBuilder {
+    fn default() -> SvmProblemBuilder {
+        SvmProblemBuilder::new()
+    }
+}
+
 impl Clone for SvmProblem {
 	fn clone(&self) -> SvmProblem {
-		SvmProblem::new(self.x.clone(), self.y.clone()).unwrap()
+		let mut prob = SvmProblem::new(self.x.clone(), self.y.clone()).unwrap();
+		prob.ids = self.ids.clone();
+		prob
 	}
+}
+
+/// Iterates the same way as SvmProblem::iter, but can be named, so `for (label, x) in &prob` works.
+impl<'a> IntoIterator for &'a SvmProblem {
+    type Item = (f64, &'a DataVec);
+    type IntoIter = Zip<Cloned<slice::Iter<'a, f64>>, slice::Iter<'a, DataVec>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.y.iter().cloned().zip(self.x.iter())
+    }
 }
\ No newline at end of file
@@ -0,0 +1,92 @@
+#[cfg(feature="timing")]
+use std::cell::Cell;
+#[cfg(feature="timing")]
+use std::time::{Duration, Instant};
+
+/// The calls that are timed when the timing feature is on.
+#[derive(Copy, Clone)]
+pub enum Timed {
+    Train,
+    Predict,
+    CrossValidation,
+}
+
+#[cfg(feature="timing")]
+thread_local! {
+    static LAST_TRAIN: Cell<Option<Duration>> = const { Cell::new(None) };
+    static LAST_PREDICT: Cell<Option<Duration>> = const { Cell::new(None) };
+    static LAST_CROSS_VALIDATION: Cell<Option<Duration>> = const { Cell::new(None) };
+}
+
+/// Runs f, recording how long it took as the latest duration of which on this thread.
+#[cfg(feature="timing")]
+pub fn record<T, F: FnOnce() -> T>(which: Timed, f: F) -> T {
+    let start = Instant::now();
+    let result = f();
+    set_last(which, Some(start.elapsed()));
+
+    result
+}
+
+// Without the feature nothing is measured, so this should compile away entirely.
+#[cfg(not(feature="timing"))]
+#[inline(always)]
+pub fn record<T, F: FnOnce() -> T>(_which: Timed, f: F) -> T {
+    f()
+}
+
+/// What record last measured on a thread, so it can be carried over to another one
+/// with last and set_last. Without the feature there's nothing to carry.
+#[cfg(feature="timing")]
+pub type Measurement = Option<Duration>;
+#[cfg(not(feature="timing"))]
+pub type Measurement = ();
+
+/// The latest duration of which recorded on this thread.
+#[cfg(feature="timing")]
+pub fn last(which: Timed) -> Measurement {
+    match which {
+        Timed::Train => LAST_TRAIN.with(|d| d.get()),
+        Timed::Predict => LAST_PREDICT.with(|d| d.get()),
+        Timed::CrossValidation => LAST_CROSS_VALIDATION.with(|d| d.get()),
+    }
+}
+
+#[cfg(not(feature="timing"))]
+#[inline(always)]
+pub fn last(_which: Timed) -> Measurement {}
+
+/// Makes measured the latest duration of which on this thread, as if record had measured it here.
+#[cfg(feature="timing")]
+pub fn set_last(which: Timed, measured: Measurement) {
+    match which {
+        Timed::Train => LAST_TRAIN.with(|d| d.set(measured)),
+        Timed::Predict => LAST_PREDICT.with(|d| d.set(measured)),
+        Timed::CrossValidation => LAST_CROSS_VALIDATION.with(|d| d.set(measured)),
+    }
+}
+
+#[cfg(not(feature="timing"))]
+#[inline(always)]
+pub fn set_last(_which: Timed, _measured: Measurement) {}
+
+/// The wall-clock duration of the last call to svm_train made by SvmProblem::train
+/// on this thread, or None if there hasn't been one.
+#[cfg(feature="timing")]
+pub fn last_train_duration() -> Option<Duration> {
+    last(Timed::Train)
+}
+
+/// The wall-clock duration of the last SvmModel::predict on this thread.
+#[cfg(feature="timing")]
+pub fn last_predict_duration() -> Option<Duration> {
+    last(Timed::Predict)
+}
+
+/// The wall-clock duration of the last cross validation on this thread, whether it was
+/// run by libsvm (cross_validation) or fold by fold in Rust (cross_validation_with_folds and
+/// cross_validation_with_progress).
+#[cfg(feature="timing")]
+pub fn last_cross_validation_duration() -> Option<Duration> {
+    last(Timed::CrossValidation)
+}